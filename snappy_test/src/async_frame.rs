@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod tests {
+    use std::io::{Write, ErrorKind};
+    use xsnappy::{FrameEncoder, AsyncFrameEncoder, AsyncFrameDecoder};
+    use xsnappy::error::{SnappyError, CorruptKind};
+    use crate::golden::read_file_to_vec;
+
+    fn block_on<F: std::future::Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(f)
+    }
+
+    /// Frame `src` with the blocking encoder, in writes of `size` bytes and
+    /// with a flush after `flush_at` bytes.
+    fn sync_frame(src: &[u8], size: usize, flush_at: usize) -> Vec<u8> {
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(&src[..flush_at]).unwrap();
+        enc.flush().unwrap();
+        for p in src[flush_at..].chunks(size) {
+            enc.write_all(p).unwrap();
+        }
+        enc.into_inner().unwrap()
+    }
+
+    fn test_tokio_duplex() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let src = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let (w, r) = tokio::io::duplex(1000);
+        let (framed, decoded) = block_on(async {
+            // Tee the compressed stream to check it against the blocking encoder.
+            let (tee_w, mut tee_r) = tokio::io::duplex(1 << 20);
+            let write = async {
+                let mut enc = AsyncFrameEncoder::new(tee_w);
+                enc.write_all(&src[..1000]).await.unwrap();
+                enc.flush().await.unwrap();
+                for p in src[1000..].chunks(7777) {
+                    enc.write_all(p).await.unwrap();
+                }
+                enc.shutdown().await.unwrap();
+            };
+            let pipe = async {
+                let mut framed = vec![];
+                let mut w = w;
+                let mut buf = [0; 4096];
+                loop {
+                    let n = tee_r.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break
+                    }
+                    framed.extend_from_slice(&buf[..n]);
+                    w.write_all(&buf[..n]).await.unwrap();
+                }
+                w.shutdown().await.unwrap();
+                framed
+            };
+            let read = async {
+                let mut decoded = vec![];
+                AsyncFrameDecoder::new(r).read_to_end(&mut decoded).await.unwrap();
+                decoded
+            };
+            let (_, framed, decoded) = tokio::join!(write, pipe, read);
+            (framed, decoded)
+        });
+        assert_eq!(decoded, src);
+        assert_eq!(framed, sync_frame(&src, 7777, 1000));
+    }
+
+    fn test_tokio_buf_read() {
+        use tokio::io::AsyncBufReadExt;
+        let src = b"line one\nline two\n".repeat(5000);
+        let framed = sync_frame(&src, src.len(), 0);
+        let lines = block_on(async {
+            let mut dec = AsyncFrameDecoder::new(&framed[..]);
+            let mut lines = 0;
+            let mut line = String::new();
+            while dec.read_line(&mut line).await.unwrap() > 0 {
+                lines += 1;
+                line.clear();
+            }
+            lines
+        });
+        assert_eq!(lines, 10000);
+    }
+
+    fn test_futures_io() {
+        use futures::io::{AsyncReadExt, AsyncWriteExt};
+        let src = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let framed = futures::executor::block_on(async {
+            let mut enc = AsyncFrameEncoder::new(futures::io::Cursor::new(vec![]));
+            enc.write_all(&src[..1000]).await.unwrap();
+            enc.flush().await.unwrap();
+            for p in src[1000..].chunks(7777) {
+                enc.write_all(p).await.unwrap();
+            }
+            enc.close().await.unwrap();
+            enc.into_inner().into_inner()
+        });
+        assert_eq!(framed, sync_frame(&src, 7777, 1000));
+
+        let decoded = futures::executor::block_on(async {
+            let mut decoded = vec![];
+            AsyncFrameDecoder::new(&framed[..]).read_to_end(&mut decoded).await.unwrap();
+            decoded
+        });
+        assert_eq!(decoded, src);
+    }
+
+    fn test_corrupt() {
+        let mut framed = sync_frame(b"hello world! hello world!", 100, 0);
+        framed[14] ^= 1;
+        let err = block_on(async {
+            let mut dst = vec![];
+            let mut dec = AsyncFrameDecoder::new(&framed[..]);
+            tokio::io::AsyncReadExt::read_to_end(&mut dec, &mut dst).await.unwrap_err()
+        });
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        match err.get_ref().unwrap().downcast_ref::<SnappyError>() {
+            Some(SnappyError::Corrupt(c)) => {
+                assert_eq!(c.kind, CorruptKind::Checksum);
+                assert_eq!(c.input_offset, 10);
+            }
+            other => panic!("unexpected error: {:?}", other)
+        }
+
+        // A truncated stream is an error, not an early end.
+        framed[14] ^= 1;
+        let err = futures::executor::block_on(async {
+            let mut dst = vec![];
+            let mut dec = AsyncFrameDecoder::new(&framed[..framed.len() - 1]);
+            futures::io::AsyncReadExt::read_to_end(&mut dec, &mut dst).await.unwrap_err()
+        });
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn it_works() {
+        test_tokio_duplex();
+        test_tokio_buf_read();
+        test_futures_io();
+        test_corrupt();
+    }
+}
@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod tests {
+    use xsnappy::builder::StreamBuilder;
+    use xsnappy::{decode_len, decompress_to_vec, validate, ops};
+    use xsnappy::error::{SnappyError, CorruptKind};
+
+    fn test_encodings() {
+        // Every literal header encodes the same literal.
+        for len_bytes in 0..=4 {
+            let block = StreamBuilder::new().literal_with_len_bytes(b"hello", len_bytes).build();
+            assert_eq!(block.len(), 1 + 1 + len_bytes + 5);
+            assert_eq!(decompress_to_vec(&block).unwrap(), b"hello");
+        }
+        // So does every copy tag.
+        let mut b = StreamBuilder::new();
+        b.literal(b"0123456789");
+        let want = b"01234567894567894567894567";
+        let copy1 = b.clone().copy1(6, 8).copy1(6, 8).build();
+        let copy2 = b.clone().copy2(6, 16).build();
+        let copy4 = b.clone().copy4(6, 10).copy4(6, 6).build();
+        for block in [copy1, copy2, copy4].iter() {
+            assert_eq!(decompress_to_vec(block).unwrap(), &want[..]);
+        }
+        assert_eq!(StreamBuilder::new().literal(&[7; 300]).build()[..4], [0xac, 0x02, 61 << 2, 43]);
+        assert_eq!(StreamBuilder::new().build(), [0]);
+    }
+
+    fn test_overlapping_copies() {
+        // Copies whose offset is shorter than their length repeat a pattern,
+        // near the end of the output and far from it.
+        for offset in 1..=20 {
+            for &tail in [0usize, 5, 40].iter() {
+                let mut b = StreamBuilder::new();
+                let pattern: Vec<u8> = (0..offset as u8).collect();
+                b.literal(&pattern).copy2(offset, 64).copy4(offset as u32, 33);
+                if tail > 0 {
+                    b.literal(&vec![0xee; tail]);
+                }
+                let block = b.build();
+                let got = decompress_to_vec(&block).unwrap();
+                assert_eq!(got.len() as u64, b.ops_len());
+                for (i, &x) in got[..offset + 97].iter().enumerate() {
+                    assert_eq!(x, pattern[i % offset]);
+                }
+            }
+        }
+    }
+
+    fn corrupt_kind(block: &[u8]) -> CorruptKind {
+        match validate(block) {
+            Err(SnappyError::Corrupt(c)) => c.kind,
+            ret => panic!("unexpected {:?}", ret)
+        }
+    }
+
+    fn test_invalid() {
+        let block = StreamBuilder::new().literal(b"abcd").copy2(0, 4).build();
+        assert_eq!(corrupt_kind(&block), CorruptKind::ZeroOffset);
+        let block = StreamBuilder::new().literal(b"abcd").copy4(70000, 4).build();
+        assert_eq!(corrupt_kind(&block), CorruptKind::OffsetTooLarge(70000));
+        let block = StreamBuilder::new().literal_header(100, 1).raw(b"short").build();
+        assert_eq!(corrupt_kind(&block), CorruptKind::LiteralPastInput(100));
+        let block = StreamBuilder::new().literal(b"abcd").decoded_len(3).build();
+        assert_eq!(corrupt_kind(&block), CorruptKind::PastDecodedLen(4));
+        let block = StreamBuilder::new().literal(b"abcd").decoded_len(5).build();
+        assert_eq!(corrupt_kind(&block), CorruptKind::ShortOutput);
+        let block = StreamBuilder::new().literal(b"abcd").raw(&[0x02]).build();
+        assert_eq!(corrupt_kind(&block), CorruptKind::TruncatedTag);
+        let block = StreamBuilder::new().decoded_len(1 << 32).build();
+        assert_eq!(corrupt_kind(&block), CorruptKind::Header);
+        assert_eq!(decode_len(&StreamBuilder::new().decoded_len(u32::MAX as u64).build()).unwrap(),
+                   u32::MAX as usize);
+    }
+
+    fn test_ops_round_trip() {
+        let block = StreamBuilder::new().literal_with_len_bytes(b"xyz", 2).copy1(3, 5).copy4(1, 2).build();
+        let lens: Vec<_> = ops(&block).unwrap().map(|op| op.unwrap().1.output_len()).collect();
+        assert_eq!(lens, vec![3, 5, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "copy1 length 12 out of 4..=11")]
+    fn test_unencodable() {
+        StreamBuilder::new().copy1(1, 12);
+    }
+
+    #[test]
+    fn it_works() {
+        test_encodings();
+        test_overlapping_copies();
+        test_invalid();
+        test_ops_round_trip();
+    }
+}
@@ -0,0 +1,114 @@
+#[cfg(test)]
+mod tests {
+    use bytes1::{Bytes, BytesMut};
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::codec::{Decoder, Encoder, FramedRead, FramedWrite};
+    use xsnappy::{SnappyCodec, compress_to_vec};
+    use xsnappy::error::{SnappyError, CorruptKind};
+    use crate::golden::read_file_to_vec;
+    use crate::stream_util::snappy_error;
+
+    fn test_format() {
+        let msg = b"hello world! hello world!";
+        let mut buf = BytesMut::new();
+        SnappyCodec::new().encode(Bytes::from_static(msg), &mut buf).unwrap();
+        let block = compress_to_vec(msg).unwrap();
+        assert_eq!(buf[0] as usize, block.len());
+        assert_eq!(&buf[1..], &block[..]);
+    }
+
+    fn test_framed() {
+        let src = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let msgs: Vec<Bytes> = src.chunks(5000).map(Bytes::copy_from_slice).chain(vec![Bytes::new()]).collect();
+        let (w, r) = tokio::io::duplex(1000);
+        let got = tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+            let write = async {
+                let mut sink = FramedWrite::new(w, SnappyCodec::new());
+                for msg in msgs.iter() {
+                    sink.send(msg.clone()).await.unwrap();
+                }
+            };
+            let read = async {
+                FramedRead::new(r, SnappyCodec::new()).map(|msg| msg.unwrap()).collect::<Vec<_>>().await
+            };
+            tokio::join!(write, read).1
+        });
+        assert_eq!(got.len(), msgs.len());
+        for (got, msg) in got.iter().zip(msgs.iter()) {
+            assert_eq!(&got[..], &msg[..]);
+        }
+    }
+
+    fn test_partial() {
+        let mut codec = SnappyCodec::new();
+        let mut full = BytesMut::new();
+        codec.encode(Bytes::from(vec![b'a'; 1000]), &mut full).unwrap();
+        codec.encode(Bytes::from_static(b"second"), &mut full).unwrap();
+        // Feed the frames a byte at a time.
+        let mut buf = BytesMut::new();
+        let mut msgs = vec![];
+        for &b in full.iter() {
+            buf.extend_from_slice(&[b]);
+            if let Some(msg) = codec.decode(&mut buf).unwrap() {
+                msgs.push(msg);
+            }
+        }
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(&msgs[0][..], &vec![b'a'; 1000][..]);
+        assert_eq!(&msgs[1][..], b"second");
+        assert!(buf.is_empty());
+    }
+
+    fn test_max_frame_len() {
+        let mut codec = SnappyCodec::with_max_frame_len(100);
+        assert_eq!(codec.max_frame_len(), 100);
+        let mut buf = BytesMut::new();
+        let err = codec.encode(Bytes::from(vec![0; 101]), &mut buf).unwrap_err();
+        assert_eq!(snappy_error(&err), SnappyError::LimitExceeded);
+        assert!(buf.is_empty());
+
+        // A frame that decodes to too much is rejected by its block header.
+        SnappyCodec::new().encode(Bytes::from(vec![0; 101]), &mut buf).unwrap();
+        assert_eq!(snappy_error(&codec.decode(&mut buf).unwrap_err()), SnappyError::LimitExceeded);
+
+        // A frame too long to be valid is rejected before it is buffered.
+        let mut buf = BytesMut::from(&b"\xff\xff\xff\x7f"[..]);
+        assert_eq!(snappy_error(&codec.decode(&mut buf).unwrap_err()), SnappyError::LimitExceeded);
+        codec.set_max_frame_len(1 << 30);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+    }
+
+    fn test_corrupt() {
+        let mut codec = SnappyCodec::new();
+        let mut buf = BytesMut::new();
+        codec.encode(Bytes::from_static(b"hello world! hello world!"), &mut buf).unwrap();
+        // Claim one more decoded byte than the block holds.
+        buf[1] += 1;
+        match snappy_error(&codec.decode(&mut buf).unwrap_err()) {
+            SnappyError::Corrupt(c) => assert_eq!(c.kind, CorruptKind::ShortOutput),
+            err => panic!("unexpected error: {:?}", err)
+        }
+
+        // Lengths that overflow 64 bits, at the 11th byte and at a 10th byte above 1.
+        let mut long = [0xff; 10];
+        long[9] = 0x02;
+        for header in [&[0xff; 11][..], &long[..]].iter() {
+            let mut buf = BytesMut::from(*header);
+            match snappy_error(&codec.decode(&mut buf).unwrap_err()) {
+                SnappyError::Corrupt(c) => assert_eq!(c.kind, CorruptKind::Header),
+                err => panic!("unexpected error: {:?}", err)
+            }
+        }
+        // Ten continuation bytes wait for more.
+        assert!(codec.decode(&mut BytesMut::from(&[0xff; 10][..])).unwrap().is_none());
+    }
+
+    #[test]
+    fn it_works() {
+        test_format();
+        test_framed();
+        test_partial();
+        test_max_frame_len();
+        test_corrupt();
+    }
+}
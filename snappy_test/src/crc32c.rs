@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod tests {
+    use xsnappy::crc32c::{crc32c, crc32c_update, mask, unmask};
+
+    /// Bit-at-a-time reference implementation.
+    fn reference(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &b in data {
+            crc ^= b as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 { (crc >> 1) ^ 0x82f63b78 } else { crc >> 1 };
+            }
+        }
+        !crc
+    }
+
+    fn test_known_values() {
+        assert_eq!(crc32c(b""), 0);
+        assert_eq!(crc32c(b"123456789"), 0xe3069283);
+        // Test vectors from RFC 3720, section B.4.
+        assert_eq!(crc32c(&[0; 32]), 0x8a9136aa);
+        assert_eq!(crc32c(&[0xff; 32]), 0x62a8ab43);
+        let inc: Vec<u8> = (0..32).collect();
+        assert_eq!(crc32c(&inc), 0x46dd794e);
+        let dec: Vec<u8> = (0..32).rev().collect();
+        assert_eq!(crc32c(&dec), 0x113fdb5c);
+    }
+
+    fn test_against_reference() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 31 % 257) as u8).collect();
+        for start in 0..9 {
+            for end in (start..data.len()).step_by(37) {
+                assert_eq!(crc32c(&data[start..end]), reference(&data[start..end]));
+            }
+        }
+    }
+
+    fn test_update() {
+        let data: Vec<u8> = (0..500u32).map(|i| (i * 13 % 251) as u8).collect();
+        let want = crc32c(&data);
+        for split in 0..data.len() {
+            let crc = crc32c_update(crc32c(&data[..split]), &data[split..]);
+            assert_eq!(crc, want);
+        }
+        assert_eq!(crc32c_update(0, &data), want);
+    }
+
+    fn test_mask() {
+        // The masked checksum of "hello" as written by other framing encoders.
+        assert_eq!(mask(crc32c(b"hello")), 0x191c1fbb);
+        for &crc in &[0, 1, 0xe3069283, 0xffffffff, 0x80000000] {
+            assert_eq!(unmask(mask(crc)), crc);
+        }
+    }
+
+    #[test]
+    fn it_works() {
+        test_known_values();
+        test_against_reference();
+        test_update();
+        test_mask();
+    }
+}
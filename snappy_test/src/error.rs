@@ -0,0 +1,117 @@
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::io::{self, Read, Write};
+    use xsnappy::{decode, decode_len, validate, decompress_to_vec, compress_to_vec, FrameEncoder, FrameDecoder};
+    use xsnappy::error::{SnappyError, Corruption, CorruptKind, TagKind};
+
+    /// A block of 600 bytes: a literal of 512 bytes, then `tail`.
+    fn block(tail: &[u8]) -> Vec<u8> {
+        let mut src = vec![0xd8, 0x04, 61 << 2, 0xff, 0x01];
+        src.extend_from_slice(&[b'a'; 512]);
+        src.extend_from_slice(tail);
+        src
+    }
+
+    fn corruption(src: &[u8]) -> Corruption {
+        let mut dst = vec![0; decode_len(src).unwrap()];
+        let err = decode(&mut dst, src).unwrap_err();
+        // validate reports the same error as decode.
+        assert_eq!(validate(src).unwrap_err(), err);
+        match err {
+            SnappyError::Corrupt(c) => c,
+            err => panic!("unexpected {:?}", err)
+        }
+    }
+
+    fn test_offset_too_large() {
+        // A copy4 of 10 bytes from offset 70000.
+        let src = block(&[9 << 2 | 3, 0x70, 0x11, 0x01, 0x00]);
+        let c = corruption(&src);
+        assert_eq!(c, Corruption {
+            kind: CorruptKind::OffsetTooLarge(70000),
+            input_offset: 517,
+            output_offset: 512,
+            tag: Some(TagKind::Copy4),
+        });
+        assert_eq!(SnappyError::Corrupt(c).to_string(),
+                   "snappy: corrupt input: copy offset 70000 exceeds produced 512 bytes at input byte 517");
+    }
+
+    fn test_kinds() {
+        let c = corruption(&block(&[0x01, 0x00]));
+        assert_eq!((c.kind, c.input_offset, c.tag), (CorruptKind::ZeroOffset, 517, Some(TagKind::Copy1)));
+
+        let c = corruption(&block(&[0x02, 0x01]));
+        assert_eq!((c.kind, c.input_offset, c.tag), (CorruptKind::TruncatedTag, 517, Some(TagKind::Copy2)));
+
+        let c = corruption(&block(&[60 << 2]));
+        assert_eq!((c.kind, c.tag), (CorruptKind::TruncatedTag, Some(TagKind::Literal)));
+
+        let c = corruption(&block(&[9 << 2, b'b']));
+        assert_eq!(c.kind, CorruptKind::LiteralPastInput(10));
+
+        let mut tail = vec![60 << 2, 99];
+        tail.extend_from_slice(&[b'b'; 100]);
+        let c = corruption(&block(&tail));
+        assert_eq!((c.kind, c.output_offset), (CorruptKind::PastDecodedLen(100), 512));
+
+        let c = corruption(&block(&[]));
+        assert_eq!(c, Corruption { kind: CorruptKind::ShortOutput, input_offset: 517, output_offset: 512, tag: None });
+        assert_eq!(SnappyError::Corrupt(c).to_string(),
+                   "snappy: corrupt input: input ends after producing 512 bytes at input byte 517");
+
+        match decode_len(&[0x80]) {
+            Err(SnappyError::Corrupt(c)) => assert_eq!(c.kind, CorruptKind::Header),
+            ret => panic!("unexpected {:?}", ret)
+        }
+        match decode_len(&[0xff; 11]) {
+            Err(SnappyError::Corrupt(c)) => assert_eq!(c.kind, CorruptKind::Header),
+            ret => panic!("unexpected {:?}", ret)
+        }
+    }
+
+    fn test_frame_offsets() {
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(&[b'x'; 70000]).unwrap();
+        let mut framed = enc.into_inner().unwrap();
+        // The second chunk starts after the stream identifier and the first chunk.
+        let first_len = framed[11] as usize | (framed[12] as usize) << 8 | (framed[13] as usize) << 16;
+        let second = 10 + 4 + first_len;
+        // Turn the first tag of its block, after the 2-byte length header, into a copy1 from offset 120.
+        framed[second + 10] = 0x01;
+        let mut dst = vec![];
+        let err = FrameDecoder::new(&framed[..]).read_to_end(&mut dst).unwrap_err();
+        match err.into_inner().unwrap().downcast::<SnappyError>().map(|e| *e) {
+            Ok(SnappyError::Corrupt(c)) => {
+                assert_eq!(c.kind, CorruptKind::OffsetTooLarge(120));
+                assert_eq!(c.input_offset, second + 10);
+                assert_eq!(c.output_offset, 65536);
+            }
+            ret => panic!("unexpected {:?}", ret)
+        }
+    }
+
+    fn test_conversions() {
+        fn read_all(src: &[u8]) -> io::Result<Vec<u8>> {
+            Ok(decompress_to_vec(src)?)
+        }
+        fn read_boxed(src: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+            Ok(decompress_to_vec(src)?)
+        }
+        let compressed = compress_to_vec(b"hello world").unwrap();
+        assert_eq!(read_all(&compressed).unwrap(), b"hello world");
+        let err = read_all(&compressed[..compressed.len()-1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("snappy: corrupt input: "));
+        assert!(read_boxed(&compressed[..3]).unwrap_err().is::<SnappyError>());
+    }
+
+    #[test]
+    fn it_works() {
+        test_offset_too_large();
+        test_kinds();
+        test_frame_offsets();
+        test_conversions();
+    }
+}
@@ -0,0 +1,298 @@
+#[cfg(test)]
+mod tests {
+    use std::io::{Write, Read, BufRead, ErrorKind};
+    use xsnappy::{FrameEncoder, FrameDecoder, Chunk, ChunkType};
+    use xsnappy::{decode_len, decode};
+    use xsnappy::error::{SnappyError, CorruptKind};
+    use crate::golden::read_file_to_vec;
+
+    const MAGIC_CHUNK: &[u8] = b"\xff\x06\x00\x00sNaPpY";
+
+    /// Split a framed stream into `(chunk_type, body)` pairs.
+    fn chunks(mut src: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut chunks = vec![];
+        while !src.is_empty() {
+            let len = src[1] as usize | (src[2] as usize) << 8 | (src[3] as usize) << 16;
+            chunks.push((src[0], src[4..4+len].to_vec()));
+            src = &src[4+len..];
+        }
+        chunks
+    }
+
+    /// Decode the data chunks of a framed stream by hand.
+    fn unframe(src: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        for (chunk_type, body) in chunks(&src[MAGIC_CHUNK.len()..]) {
+            match chunk_type {
+                0x00 => {
+                    let mut dst = vec![0; decode_len(&body[4..]).unwrap()];
+                    decode(&mut dst, &body[4..]).unwrap();
+                    data.extend_from_slice(&dst);
+                }
+                0x01 => data.extend_from_slice(&body[4..]),
+                _ => unreachable!()
+            }
+        }
+        data
+    }
+
+    fn test_encode_short() {
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(b"hello").unwrap();
+        let dst = enc.into_inner().unwrap();
+        assert_eq!(&dst[..10], MAGIC_CHUNK);
+        // Incompressible data is stored as an uncompressed chunk.
+        assert_eq!(&dst[10..14], &[0x01, 9, 0, 0]);
+        assert_eq!(&dst[14..18], &[0xbb, 0x1f, 0x1c, 0x19]);
+        assert_eq!(&dst[18..], b"hello");
+    }
+
+    fn test_encode_golden() {
+        let src = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(&src).unwrap();
+        let dst = enc.into_inner().unwrap();
+        let cs = chunks(&dst);
+        assert_eq!(cs[0], (0xff, b"sNaPpY".to_vec()));
+        assert!(cs[1..].iter().all(|c| c.0 == 0x00));
+        assert_eq!(unframe(&dst), src);
+    }
+
+    fn test_encode_split_chunks() {
+        let src: Vec<u8> = (0..200000u32).map(|i| (i % 251) as u8).collect();
+        let mut enc = FrameEncoder::new(vec![]);
+        for p in src.chunks(1000) {
+            enc.write_all(p).unwrap();
+        }
+        let dst = enc.into_inner().unwrap();
+        let cs = chunks(&dst);
+        assert_eq!(cs.len(), 1 + 4);
+        assert_eq!(unframe(&dst), src);
+    }
+
+    /// Return the uncompressed lengths of the data chunks of a framed stream.
+    fn chunk_lens(src: &[u8]) -> Vec<usize> {
+        chunks(&src[MAGIC_CHUNK.len()..]).iter().map(|(chunk_type, body)| match chunk_type {
+            0x00 => decode_len(&body[4..]).unwrap(),
+            _ => body.len() - 4
+        }).collect()
+    }
+
+    fn test_encode_large_writes() {
+        // Like Go's Writer, a large write with nothing buffered is emitted right away,
+        // the last chunk included, while a smaller write is buffered.
+        let src: Vec<u8> = (0..400000u32).map(|i| (i % 251) as u8).collect();
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(&src[..65536 * 2 + 100]).unwrap();
+        assert_eq!(chunk_lens(enc.get_ref()), [65536, 65536, 100]);
+        enc.write_all(&src[65536 * 2 + 100..140000]).unwrap();
+        assert_eq!(chunk_lens(enc.get_ref()).len(), 3);
+        // A write that overflows the buffer fills it, and the rest is written like above.
+        enc.write_all(&src[140000..300000]).unwrap();
+        assert_eq!(chunk_lens(enc.get_ref()), [65536, 65536, 100, 65536, 65536, 37756]);
+        enc.write_all(&src[300000..]).unwrap();
+        let dst = enc.into_inner().unwrap();
+        assert_eq!(chunk_lens(&dst), [65536, 65536, 100, 65536, 65536, 37756, 65536, 34464]);
+        assert_eq!(unframe(&dst), src);
+
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(&src[..70000]).unwrap();
+        enc.write_all(&src[70000..70010]).unwrap();
+        assert_eq!(chunk_lens(&enc.into_inner().unwrap()), [65536, 4464, 10]);
+    }
+
+    fn test_encode_flush() {
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(b"hello").unwrap();
+        assert!(enc.get_ref().is_empty());
+        enc.flush().unwrap();
+        assert_eq!(enc.get_ref().len(), 10 + 8 + 5);
+        enc.flush().unwrap();
+        assert_eq!(enc.get_ref().len(), 10 + 8 + 5);
+        enc.write_all(b" world").unwrap();
+        let dst = enc.into_inner().unwrap();
+        assert_eq!(chunks(&dst).len(), 3);
+        assert_eq!(unframe(&dst), b"hello world");
+    }
+
+    fn test_encode_reset() {
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(b"discarded").unwrap();
+        let old = enc.reset(vec![]);
+        assert!(old.is_empty());
+        enc.write_all(b"hello").unwrap();
+        let dst = enc.into_inner().unwrap();
+        assert_eq!(&dst[..10], MAGIC_CHUNK);
+        assert_eq!(unframe(&dst), b"hello");
+    }
+
+    fn test_encode_empty() {
+        let enc = FrameEncoder::new(vec![]);
+        assert!(enc.into_inner().unwrap().is_empty());
+    }
+
+    fn frame(src: &[u8]) -> Vec<u8> {
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(src).unwrap();
+        enc.into_inner().unwrap()
+    }
+
+    fn unframe_reader(src: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut dst = vec![];
+        FrameDecoder::new(src).read_to_end(&mut dst)?;
+        Ok(dst)
+    }
+
+    fn test_decode_golden() {
+        let src = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        assert_eq!(unframe_reader(&frame(&src)).unwrap(), src);
+
+        let src: Vec<u8> = (0..300000u32).map(|i| (i * 7 % 253) as u8).collect();
+        assert_eq!(unframe_reader(&frame(&src)).unwrap(), src);
+    }
+
+    fn test_decode_buf_read() {
+        let framed = frame(b"line one\nline two\n");
+        let mut dec = FrameDecoder::new(&framed[..]);
+        let mut line = String::new();
+        dec.read_line(&mut line).unwrap();
+        assert_eq!(line, "line one\n");
+        line.clear();
+        dec.read_line(&mut line).unwrap();
+        assert_eq!(line, "line two\n");
+        assert!(dec.fill_buf().unwrap().is_empty());
+    }
+
+    fn test_decode_skippable() {
+        let mut framed = frame(b"hello");
+        // Padding and a reserved skippable chunk between two streams.
+        framed.extend_from_slice(&[0xfe, 3, 0, 0, 0, 0, 0]);
+        framed.extend_from_slice(&[0x80, 2, 0, 0, 1, 2]);
+        framed.extend_from_slice(&frame(b" world"));
+        assert_eq!(unframe_reader(&framed).unwrap(), b"hello world");
+    }
+
+    fn test_decode_unskippable() {
+        let mut framed = frame(b"hello");
+        framed.extend_from_slice(&[0x02, 2, 0, 0, 1, 2]);
+        let err = unframe_reader(&framed).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "snappy: unsupported reserved unskippable chunk");
+    }
+
+    fn test_decode_corrupt() {
+        // Bad checksum.
+        let mut framed = frame(b"hello");
+        framed[14] ^= 1;
+        let err = unframe_reader(&framed).unwrap_err();
+        assert_eq!(err.to_string(), "snappy: corrupt input: checksum mismatch at input byte 10");
+        match err.get_ref().and_then(|e| e.downcast_ref::<SnappyError>()) {
+            Some(SnappyError::Corrupt(c)) => assert_eq!(c.kind, CorruptKind::Checksum),
+            _ => unreachable!()
+        }
+        // Missing stream identifier.
+        let framed = frame(b"hello");
+        assert!(unframe_reader(&framed[10..]).is_err());
+        // Truncated chunk.
+        let framed = frame(b"hello");
+        assert!(unframe_reader(&framed[..framed.len()-1]).is_err());
+        // Truncated chunk header.
+        assert!(unframe_reader(&framed[..12]).is_err());
+        // Empty stream.
+        assert!(unframe_reader(&[]).unwrap().is_empty());
+    }
+
+    fn test_chunk_iter() {
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let text = text.repeat(5);
+        let mut framed = frame(&text[..70000]);
+        framed.extend_from_slice(&[0xfe, 3, 0, 0, 0, 0, 0]);
+        framed.extend_from_slice(&[0x80, 2, 0, 0, 1, 2]);
+        let list: Vec<Chunk> = xsnappy::chunks(&framed).map(Result::unwrap).collect();
+        let types: Vec<ChunkType> = list.iter().map(|c| c.chunk_type).collect();
+        assert_eq!(types[0], ChunkType::StreamIdentifier);
+        assert_eq!(&types[types.len() - 2..], &[ChunkType::Padding, ChunkType::Skippable(0x80)]);
+        assert_eq!(list.iter().map(|c| c.decoded_len).sum::<usize>(), 70000);
+        let mut pos = 0;
+        for (c, (chunk_type, body)) in list.iter().zip(chunks(&framed)) {
+            assert_eq!((c.offset, c.body), (pos, &body[..]));
+            assert!(chunk_type != 0x00 || c.chunk_type == ChunkType::Compressed);
+            pos += 4 + body.len();
+        }
+
+        // The iterator fails exactly where `FrameDecoder` does, with the same error.
+        let framed = frame(&text[..1000]);
+        let mut damaged = vec![framed[10..].to_vec(), framed[..framed.len() - 1].to_vec(), framed[..12].to_vec()];
+        for &(i, x) in [(0, 1), (4, 1), (10, 1), (11, 0xff), (13, 1), (14, 1), (20, 0x40), (100, 1)].iter() {
+            let mut bad = framed.clone();
+            bad[i] ^= x;
+            damaged.push(bad);
+        }
+        let mut big = framed.clone();
+        big[11..14].copy_from_slice(&[0xff, 0xff, 0x00]);
+        damaged.push(big);
+        let mut unskippable = framed.clone();
+        unskippable.extend_from_slice(&[0x02, 2, 0, 0, 1, 2]);
+        damaged.push(unskippable);
+        for bad in damaged.iter() {
+            let err = xsnappy::chunks(bad).find_map(Result::err).unwrap();
+            let want = unframe_reader(bad).unwrap_err();
+            assert_eq!(err.to_string(), want.to_string());
+        }
+        // With `ignore_checksums`, a mismatch is reported on its chunk and the later chunks follow.
+        let mut bad = frame(&text.repeat(3)[..200000]);
+        let second = 10 + 4 + (bad[11] as usize | (bad[12] as usize) << 8 | (bad[13] as usize) << 16);
+        bad[second + 4] ^= 1;
+        assert_eq!(xsnappy::chunks(&bad).find_map(Result::err).unwrap().to_string(),
+                   unframe_reader(&bad).unwrap_err().to_string());
+        let list: Vec<Chunk> = xsnappy::chunks(&bad).ignore_checksums().map(Result::unwrap).collect();
+        assert_eq!(list.len(), 5);
+        assert_eq!(list.iter().map(|c| c.crc_ok).collect::<Vec<_>>(), [true, true, false, true, true]);
+        assert_eq!((list[2].offset, list[2].decoded_len), (second, 65536));
+        assert_eq!(list.iter().map(|c| c.decoded_len).sum::<usize>(), 200000);
+        // Other errors still stop it.
+        let truncated = &bad[..bad.len() - 1];
+        assert!(xsnappy::chunks(truncated).ignore_checksums().last().unwrap().is_err());
+
+        // A compressed chunk decoding to more than 64 KiB is an invalid length.
+        let block = xsnappy::compress_to_vec(&[0; 70000]).unwrap();
+        let mut bad = MAGIC_CHUNK.to_vec();
+        bad.extend_from_slice(&[0x00, (block.len() + 4) as u8, 0, 0, 0, 0, 0, 0]);
+        bad.extend_from_slice(&block);
+        match xsnappy::chunks(&bad).find_map(Result::err) {
+            Some(SnappyError::Corrupt(c)) => assert_eq!((c.kind, c.input_offset), (CorruptKind::ChunkLength, 10)),
+            e => panic!("unexpected {:?}", e)
+        }
+        assert_eq!(xsnappy::chunks(&[]).count(), 0);
+    }
+
+    fn test_decode_reset() {
+        let first = frame(b"first");
+        let second = frame(b"second");
+        let mut dec = FrameDecoder::new(&first[..]);
+        let mut buf = [0; 2];
+        dec.read_exact(&mut buf).unwrap();
+        dec.reset(&second[..]);
+        let mut dst = vec![];
+        dec.read_to_end(&mut dst).unwrap();
+        assert_eq!(dst, b"second");
+    }
+
+    #[test]
+    fn it_works() {
+        test_encode_short();
+        test_encode_golden();
+        test_encode_split_chunks();
+        test_encode_large_writes();
+        test_encode_flush();
+        test_encode_reset();
+        test_encode_empty();
+        test_decode_golden();
+        test_decode_buf_read();
+        test_decode_skippable();
+        test_decode_unskippable();
+        test_decode_corrupt();
+        test_chunk_iter();
+        test_decode_reset();
+    }
+}
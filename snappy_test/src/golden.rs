@@ -8,6 +8,15 @@ pub fn read_file_to_vec(filename: &str) -> Vec<u8> {
     data
 }
 
+/// Read the fixture `filename`, or return `None` if it hasn't been generated.
+pub fn read_fixture(filename: &str) -> Option<Vec<u8>> {
+    if !std::path::Path::new(filename).exists() {
+        eprintln!("{} is missing, skipped", filename);
+        return None
+    }
+    Some(read_file_to_vec(filename))
+}
+
 pub fn cmp(dst: &[u8], src: &[u8]) -> bool {
     if dst.len() != src.len() {
         assert_eq!(dst.len(), src.len());
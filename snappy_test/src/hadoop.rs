@@ -0,0 +1,143 @@
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, BufRead, Write};
+    use xsnappy::hadoop::{HadoopReader, HadoopWriter};
+    use xsnappy::error::{SnappyError, CorruptKind};
+    use xsnappy::{compress_to_vec, DecodeOptions};
+    use crate::golden::read_file_to_vec;
+    use crate::stream_util::{assert_error_sticks, corruption, put_be_chunk, snappy_error};
+
+    fn write(buffer_size: usize, src: &[u8], size: usize) -> Vec<u8> {
+        let mut w = HadoopWriter::with_buffer_size(vec![], buffer_size);
+        for p in src.chunks(size) {
+            w.write_all(p).unwrap();
+        }
+        w.into_inner().unwrap()
+    }
+
+    fn read(buffer_size: usize, src: &[u8]) -> io::Result<Vec<u8>> {
+        let mut dst = vec![];
+        HadoopReader::with_buffer_size(src, buffer_size).read_to_end(&mut dst)?;
+        Ok(dst)
+    }
+
+    /// Append a block of `chunks`, each a raw Snappy block, with the length `len`.
+    fn put_block(dst: &mut Vec<u8>, len: u32, chunks: &[&[u8]]) {
+        dst.extend_from_slice(&len.to_be_bytes());
+        for chunk in chunks.iter() {
+            put_be_chunk(dst, chunk);
+        }
+    }
+
+    fn test_round_trip() {
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt").repeat(40);
+        for &(buffer_size, size) in [(256 * 1024, 1 << 20), (256 * 1024, 1000), (64 * 1024, 70000), (64, 7)].iter() {
+            let src = if buffer_size < 1024 { &text[..20000] } else { &text[..] };
+            let stream = write(buffer_size, src, size);
+            assert_eq!(read(buffer_size, &stream).unwrap(), src);
+            // Every block holds one chunk of the max input length, but the last.
+            let max_input = buffer_size - (buffer_size / 6 + 32);
+            let first = u32::from_be_bytes([stream[0], stream[1], stream[2], stream[3]]) as usize;
+            assert_eq!(first, std::cmp::min(max_input, src.len()));
+        }
+        assert_eq!(write(1024, b"", 1), b"");
+        assert_eq!(read(1024, b"").unwrap(), b"");
+
+        // Flushing emits a block.
+        let mut w = HadoopWriter::new(vec![]);
+        w.write_all(b"line one\n").unwrap();
+        w.flush().unwrap();
+        assert_eq!(w.get_ref().len(), 8 + compress_to_vec(b"line one\n").unwrap().len());
+        w.write_all(b"line two\n").unwrap();
+        let stream = w.into_inner().unwrap();
+        let lines: Vec<String> = HadoopReader::new(&stream[..]).lines().map(|l| l.unwrap()).collect();
+        assert_eq!(lines, vec!["line one", "line two"]);
+    }
+
+    fn test_hadoop_blocks() {
+        // Blocks of several chunks, as Hadoop writes large writes, and the empty
+        // block Hadoop writes when it finishes a stream with nothing buffered.
+        let mut stream = vec![];
+        let a = compress_to_vec(b"hello ").unwrap();
+        let b = compress_to_vec(b"world").unwrap();
+        put_block(&mut stream, 11, &[&a, &b]);
+        put_block(&mut stream, 0, &[]);
+        put_block(&mut stream, 5, &[&b]);
+        put_block(&mut stream, 0, &[]);
+        assert_eq!(read(1024, &stream).unwrap(), b"hello worldworld");
+    }
+
+    fn test_corrupt() {
+        let a = compress_to_vec(b"hello ").unwrap();
+        let big = compress_to_vec(&[7; 2000]).unwrap();
+
+        // Cut off inside the lengths and the chunks, but not between blocks.
+        let mut stream = vec![];
+        put_block(&mut stream, 12, &[&a, &a]);
+        for i in 1..stream.len() {
+            let err = read(1024, &stream[..i]).unwrap_err();
+            assert_eq!(corruption(&err).0, CorruptKind::TruncatedChunk, "cut at {}", i);
+        }
+
+        // A chunk that decodes past its block.
+        let mut stream = vec![];
+        put_block(&mut stream, 5, &[&a]);
+        assert_eq!(corruption(&read(1024, &stream).unwrap_err()), (CorruptKind::ChunkLength, 4));
+
+        // Chunks longer than the buffer size, decoded or compressed.
+        let mut stream = vec![];
+        put_block(&mut stream, 6, &[&a]);
+        put_block(&mut stream, 2000, &[&big]);
+        assert_eq!(corruption(&read(1024, &stream).unwrap_err()), (CorruptKind::ChunkLength, 12 + a.len()));
+        assert_eq!(read(4096, &stream).unwrap().len(), 2006);
+        let mut stream = vec![];
+        put_block(&mut stream, 10, &[&[0; 2000]]);
+        assert_eq!(corruption(&read(1024, &stream).unwrap_err()), (CorruptKind::ChunkLength, 4));
+
+        // A corrupt Snappy block, at its offset in the stream. The error sticks.
+        let mut stream = vec![];
+        put_block(&mut stream, 6, &[&a]);
+        put_block(&mut stream, 6, &[&[6, 0x10, b'x']]);
+        let mut r = HadoopReader::new(&stream[..]);
+        let mut dst = vec![];
+        let err = r.read_to_end(&mut dst).unwrap_err();
+        assert_eq!(dst, b"hello ");
+        let c = match snappy_error(&err) {
+            SnappyError::Corrupt(c) => c,
+            e => panic!("unexpected error {:?}", e)
+        };
+        assert_eq!(c.kind, CorruptKind::LiteralPastInput(5));
+        assert_eq!((c.input_offset, c.output_offset), (12 + a.len() + 4 + 1, 6));
+        assert_error_sticks(&mut r, &err);
+    }
+
+    fn test_limits() {
+        // The limits of `DecodeOptions`, on the whole stream and on every chunk.
+        let src = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let stream = write(4096, &src, 1 << 20);
+        let options = DecodeOptions { max_decoded_len: Some(10000), ..DecodeOptions::default() };
+        let mut r = HadoopReader::with_options(&stream[..], 4096, options);
+        let mut dst = vec![];
+        let err = r.read_to_end(&mut dst).unwrap_err();
+        assert_eq!(snappy_error(&err), SnappyError::LimitExceeded);
+        assert!(dst.len() <= 10000);
+        assert_error_sticks(&mut r, &err);
+        let options = DecodeOptions { max_decoded_len: Some(src.len()), ..DecodeOptions::default() };
+        let mut dst = vec![];
+        HadoopReader::with_options(&stream[..], 4096, options).read_to_end(&mut dst).unwrap();
+        assert_eq!(dst, src);
+
+        let zeros = write(4096, &[0; 10000], 1 << 20);
+        let options = DecodeOptions { max_ratio: Some(10), ..DecodeOptions::default() };
+        let err = HadoopReader::with_options(&zeros[..], 4096, options).read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(snappy_error(&err), SnappyError::LimitExceeded);
+    }
+
+    #[test]
+    fn it_works() {
+        test_round_trip();
+        test_hadoop_blocks();
+        test_corrupt();
+        test_limits();
+    }
+}
@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod tests {
+    use xsnappy::{encode_with_options, max_encode_len, encode, decompress_to_vec};
+    use xsnappy::{Encoder, EncodeOptions, CompressionLevel};
+    use xsnappy::error::SnappyError;
+    use crate::golden::read_file_to_vec;
+
+    const LEVELS: [CompressionLevel; 3] = [CompressionLevel::Fastest, CompressionLevel::Default,
+                                           CompressionLevel::Better];
+
+    fn compress(src: &[u8], level: CompressionLevel) -> Vec<u8> {
+        let mut dst = vec![0; max_encode_len(src.len())];
+        let len = encode_with_options(&mut dst, src, &EncodeOptions { level }).unwrap();
+        dst.truncate(len);
+        dst
+    }
+
+    fn test_roundtrip() {
+        let mut inputs: Vec<Vec<u8>> = ["html", "urls.10K", "fireworks.jpeg", "paper-100k.pdf",
+                                         "alice29.txt", "geo.protodata", "kppkn.gtb"].iter()
+            .map(|name| read_file_to_vec(&format!("testdata/bench/{}", name)))
+            .collect();
+        inputs.push(vec![]);
+        inputs.push(b"a".to_vec());
+        inputs.push(vec![0; 17]);
+        inputs.push(vec![0; 300000]);
+        inputs.push((0..200000u32).map(|i| (i % 7 + i / 1000 % 3) as u8).collect());
+        for src in inputs.iter() {
+            for &level in LEVELS.iter() {
+                let compressed = compress(src, level);
+                assert_eq!(&decompress_to_vec(&compressed).unwrap(), src);
+                let mut enc = Encoder::with_options(EncodeOptions { level });
+                assert_eq!(enc.compress_vec(src).unwrap(), compressed);
+            }
+        }
+    }
+
+    fn test_default_level() {
+        let src = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let mut want = vec![0; max_encode_len(src.len())];
+        let len = encode(&mut want, &src);
+        want.truncate(len);
+        assert_eq!(compress(&src, CompressionLevel::Default), want);
+        assert_eq!(EncodeOptions::default().level, CompressionLevel::Default);
+    }
+
+    fn test_better_ratio() {
+        for name in ["alice29.txt", "html", "urls.10K", "kppkn.gtb"].iter() {
+            let src = read_file_to_vec(&format!("testdata/bench/{}", name));
+            let better = compress(&src, CompressionLevel::Better).len();
+            let default = compress(&src, CompressionLevel::Default).len();
+            assert!(better * 100 < default * 95, "{}: {} vs {}", name, better, default);
+        }
+    }
+
+    fn test_better_dst_too_small() {
+        let src = read_file_to_vec("testdata/bench/alice29.txt");
+        let len = compress(&src, CompressionLevel::Better).len();
+        let mut dst = vec![0; len - 1];
+        let options = EncodeOptions { level: CompressionLevel::Better };
+        match encode_with_options(&mut dst, &src, &options) {
+            Err(SnappyError::DstTooSmall) => {}
+            _ => unreachable!()
+        }
+    }
+
+    #[test]
+    fn it_works() {
+        test_roundtrip();
+        test_default_level();
+        test_better_ratio();
+        test_better_dst_too_small();
+    }
+}
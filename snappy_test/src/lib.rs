@@ -1,6 +1,24 @@
 mod example;
 mod golden;
 mod bench;
+mod frame;
+mod crc32c;
+mod vec;
+mod level;
+mod limit;
+mod error;
+mod ops;
+mod builder;
+mod async_frame;
+mod codec;
+mod parallel;
+mod scratch;
+mod hadoop;
+mod xerial;
+mod s2;
+mod s2_stream;
+#[cfg(test)]
+mod stream_util;
 use criterion::{criterion_main, criterion_group};
 
 criterion_group!(benches, bench::run_all_benches);
@@ -9,8 +27,8 @@ criterion_main!(benches);
 #[cfg(test)]
 mod tests {
     use bytes::{BytesMut, BufMut};
-    use xsnappy::{encode, max_encode_len};
-    use xsnappy::{decode_len, decode};
+    use xsnappy::{encode, max_encode_len, try_encode, Encoder};
+    use xsnappy::{decode_len, decode, Decoder, validate};
     use xsnappy::error::SnappyError;
     use std::hint::unreachable_unchecked;
     use crate::golden::read_file_to_vec;
@@ -125,6 +143,47 @@ mod tests {
         assert_eq!(dst[15737], 0);
     }
 
+    fn test_try_encode() {
+        let mut long = BytesMut::from("");
+        for i in custom_curve(65537 * 5 / 4).iter() {
+            long.put_u32_le(*i)
+        }
+        let mid = BytesMut::from("You know some birds are not meant to be caged, \
+                                   their feathers are just too bright.");
+        for src in [&b""[..], &b"hello"[..], &mid[..], &long[..]].iter() {
+            let mut len = 0 as usize;
+            let want = test_encode(src, &mut len);
+            let mut dst = vec![0; len];
+            assert_eq!(try_encode(&mut dst, src).unwrap(), len);
+            assert_eq!(&dst[..], &want[..len]);
+            for short in (0..len).step_by(len / 50 + 1).chain(len-1..len) {
+                let mut dst = vec![0; short];
+                match try_encode(&mut dst, src) {
+                    Err(SnappyError::DstTooSmall) => {}
+                    _ => unreachable!()
+                }
+            }
+        }
+    }
+
+    fn test_reuse_encoder() {
+        let mut long = BytesMut::from("");
+        for i in custom_curve(65537 * 5 / 4).iter() {
+            long.put_u32_le(*i)
+        }
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let mut enc = Encoder::new();
+        // Alternate big and small inputs so that stale table entries are left behind.
+        for src in [&long[..], &b"hello"[..], &text[..300], &long[..70000], &text[..], &text[1000..1500]].iter() {
+            let mut len = 0 as usize;
+            let want = test_encode(src, &mut len);
+            let mut dst = vec![0; len];
+            assert_eq!(enc.compress(&mut dst, src).unwrap(), len);
+            assert_eq!(&dst[..], &want[..len]);
+            assert_eq!(&enc.compress_vec(src).unwrap()[..], &want[..len]);
+        }
+    }
+
     fn test_decode(src: &[u8]) -> BytesMut {
         let de_len = match decode_len(&src[..]) {
             Ok(len) => len,
@@ -183,6 +242,56 @@ mod tests {
         assert_eq!(dst[dst.len()/4], 3);
     }
 
+    fn test_reuse_decoder() {
+        let mut long = BytesMut::from("");
+        for i in custom_curve(65537 * 5 / 4).iter() {
+            long.put_u32_le(*i)
+        }
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let mut dec = Decoder::new();
+        for src in [&long[..], &b"hello"[..], &text[..300], &b""[..], &text[..]].iter() {
+            let mut len = 0 as usize;
+            let en_dst = test_encode(src, &mut len);
+            assert_eq!(dec.decompress(&en_dst[..len]).unwrap(), *src);
+        }
+        let mut len = 0 as usize;
+        let en_dst = test_encode(&text, &mut len);
+        match dec.decompress(&en_dst[..len-1]) {
+            Err(SnappyError::Corrupt(_)) => {}
+            _ => unreachable!()
+        }
+        assert_eq!(dec.decompress(&en_dst[..len]).unwrap(), &text[..]);
+    }
+
+    fn test_validate() {
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let mut len = 0 as usize;
+        let en_dst = test_encode(&text[..2000], &mut len);
+        let en = &en_dst[..len];
+        assert_eq!(validate(en).unwrap(), 2000);
+        assert_eq!(validate(&[0]).unwrap(), 0);
+        assert!(validate(&[]).is_err());
+
+        // validate must agree with decode on damaged data.
+        for i in 0..en.len() {
+            for &x in [0x01u8, 0x04, 0x80, 0xff].iter() {
+                let mut bad = en.to_vec();
+                bad[i] ^= x;
+                let want = decode_len(&bad).and_then(|n| {
+                    if n > 4000 { return Err(SnappyError::DecodeTooLarge) }
+                    let mut dst = vec![0; n];
+                    decode(&mut dst, &bad)
+                });
+                match (validate(&bad), want) {
+                    (Ok(a), Ok(b)) => assert_eq!(a, b),
+                    (Err(_), Err(_)) => {}
+                    (a, b) => panic!("byte {} ^ {}: {:?} vs {:?}", i, x, a, b)
+                }
+            }
+            assert!(validate(&en[..i]).is_err());
+        }
+    }
+
     fn test_corrupt() {
         let mut src = BytesMut::from("You know some birds are not meant to be caged, \
                                         their feathers are just too bright.");
@@ -201,7 +310,7 @@ mod tests {
             Ok(len) => unreachable!(),
             Err(e) => {
                 match e {
-                    SnappyError::Corrupt => assert!(true),
+                    SnappyError::Corrupt(_) => assert!(true),
                     _ => assert!(false)
                 }
             }
@@ -215,9 +324,13 @@ mod tests {
         test_empty_encode();
         test_long_encode();
         test_long_long_encode();
+        test_try_encode();
+        test_reuse_encoder();
         test_short_decode();
         test_mid_decode();
         test_long_decode();
+        test_reuse_decoder();
+        test_validate();
         test_corrupt();
     }
 }
\ No newline at end of file
@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use xsnappy::{decode_with_options, decompress_to_vec_with_options, decompress_into_with_options,
+                  compress_to_vec, Decoder, DecodeOptions, FrameEncoder, FrameDecoder};
+    use xsnappy::error::SnappyError;
+
+    fn assert_limit_exceeded<T: std::fmt::Debug>(ret: Result<T, SnappyError>) {
+        match ret {
+            Err(SnappyError::LimitExceeded) => {}
+            ret => panic!("unexpected {:?}", ret)
+        }
+    }
+
+    fn test_bomb() {
+        // A header that claims 4 GiB followed by nothing.
+        let bomb = [0xff, 0xff, 0xff, 0xff, 0x0f, 0x00];
+        let options = DecodeOptions { max_decoded_len: Some(1 << 20), max_ratio: None };
+        assert_limit_exceeded(decompress_to_vec_with_options(&bomb, &options));
+        let mut dst = vec![];
+        assert_limit_exceeded(decompress_into_with_options(&mut dst, &bomb, &options));
+        assert_eq!(dst.capacity(), 0);
+        assert_limit_exceeded(Decoder::with_options(options).decompress(&bomb));
+
+        let options = DecodeOptions { max_decoded_len: None, max_ratio: Some(100) };
+        assert_limit_exceeded(decompress_to_vec_with_options(&bomb, &options));
+    }
+
+    fn test_limits() {
+        let src = vec![b'x'; 10000];
+        let compressed = compress_to_vec(&src).unwrap();
+        let ratio = src.len() / compressed.len();
+
+        let options = DecodeOptions { max_decoded_len: Some(10000), max_ratio: Some(ratio + 1) };
+        assert_eq!(decompress_to_vec_with_options(&compressed, &options).unwrap(), src);
+        let mut dst = vec![0; 10000];
+        assert_eq!(decode_with_options(&mut dst, &compressed, &options).unwrap(), 10000);
+        assert_eq!(Decoder::with_options(options).decompress(&compressed).unwrap(), &src[..]);
+
+        let options = DecodeOptions { max_decoded_len: Some(9999), max_ratio: None };
+        assert_limit_exceeded(decompress_to_vec_with_options(&compressed, &options));
+        assert_limit_exceeded(decode_with_options(&mut dst, &compressed, &options));
+
+        let options = DecodeOptions { max_decoded_len: None, max_ratio: Some(ratio - 1) };
+        assert_limit_exceeded(decompress_to_vec_with_options(&compressed, &options));
+        assert_limit_exceeded(Decoder::with_options(options).decompress(&compressed));
+
+        assert_eq!(DecodeOptions::default(), DecodeOptions { max_decoded_len: None, max_ratio: None });
+    }
+
+    fn test_frame_limits() {
+        let src = vec![b'x'; 300000];
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(&src).unwrap();
+        let framed = enc.into_inner().unwrap();
+
+        let read = |options: DecodeOptions| {
+            let mut dst = vec![];
+            FrameDecoder::with_options(&framed[..], options).read_to_end(&mut dst).map(|_| dst)
+        };
+        assert_eq!(read(DecodeOptions { max_decoded_len: Some(300000), max_ratio: Some(25) }).unwrap(), src);
+        let err = read(DecodeOptions { max_decoded_len: Some(299999), max_ratio: None }).unwrap_err();
+        assert_eq!(err.to_string(), "snappy: decoded data exceeds the limit");
+        assert!(read(DecodeOptions { max_decoded_len: None, max_ratio: Some(10) }).is_err());
+    }
+
+    #[test]
+    fn it_works() {
+        test_bomb();
+        test_limits();
+        test_frame_limits();
+    }
+}
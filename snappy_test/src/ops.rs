@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod tests {
+    use xsnappy::{ops, compress_to_vec, Op};
+    use xsnappy::error::{SnappyError, CorruptKind, TagKind};
+    use crate::golden::read_file_to_vec;
+
+    /// Decode `src` by replaying its operations.
+    fn replay(src: &[u8]) -> Vec<u8> {
+        let mut dst = vec![];
+        for op in ops(src).unwrap() {
+            let (pos, op) = op.unwrap();
+            assert_eq!(pos, dst.len());
+            match op {
+                Op::Literal { len, input_range } => {
+                    assert_eq!(input_range.len(), len);
+                    dst.extend_from_slice(&src[input_range]);
+                }
+                Op::Copy { offset, len, .. } => {
+                    for _ in 0..len {
+                        dst.push(dst[dst.len() - offset]);
+                    }
+                }
+            }
+        }
+        dst
+    }
+
+    fn test_replay() {
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let compressed = compress_to_vec(&text).unwrap();
+        assert_eq!(ops(&compressed).unwrap().decoded_len(), text.len());
+        assert_eq!(replay(&compressed), text);
+        assert!(replay(&[0]).is_empty());
+    }
+
+    fn test_ops() {
+        // "abcd" then a copy2 of 8 bytes from offset 4, a copy1 of 4 bytes from offset 2
+        // and a copy4 of 1 byte from offset 1.
+        let src = [17, 3 << 2, b'a', b'b', b'c', b'd', 7 << 2 | 2, 4, 0, 0x01, 2, 0x03, 1, 0, 0, 0];
+        let got: Vec<_> = ops(&src).unwrap().map(|op| op.unwrap()).collect();
+        assert_eq!(got, vec![
+            (0, Op::Literal { len: 4, input_range: 2..6 }),
+            (4, Op::Copy { offset: 4, len: 8, tag: TagKind::Copy2 }),
+            (12, Op::Copy { offset: 2, len: 4, tag: TagKind::Copy1 }),
+            (16, Op::Copy { offset: 1, len: 1, tag: TagKind::Copy4 }),
+        ]);
+        assert_eq!(got[1].1.output_len(), 8);
+    }
+
+    fn test_corrupt() {
+        let compressed = compress_to_vec(b"hello world! hello world!").unwrap();
+        let mut it = ops(&compressed[..compressed.len()-1]).unwrap();
+        let mut last = None;
+        for op in &mut it {
+            last = Some(op);
+        }
+        match last {
+            Some(Err(SnappyError::Corrupt(c))) => assert_eq!(c.kind, CorruptKind::LiteralPastInput(25)),
+            ret => panic!("unexpected {:?}", ret)
+        }
+        // The iterator stops at the first error.
+        assert!(it.next().is_none());
+        assert!(ops(&[]).is_err());
+    }
+
+    #[test]
+    fn it_works() {
+        test_replay();
+        test_ops();
+        test_corrupt();
+    }
+}
@@ -0,0 +1,180 @@
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, BufRead, Write};
+    use xsnappy::{encode_parallel, compress_to_vec, FrameEncoder, ParallelFrameEncoder};
+    use xsnappy::{FrameDecoder, ParallelFrameDecoder, DecodeOptions};
+    use xsnappy::error::SnappyError;
+    use crate::golden::read_file_to_vec;
+    use crate::stream_util::assert_error_sticks;
+
+    /// Inputs of many sizes around the block size, with compressible and random parts.
+    fn inputs() -> Vec<Vec<u8>> {
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt").repeat(4);
+        let mut x = 1u32;
+        let noise: Vec<u8> = (0..300000).map(|_| { x ^= x << 13; x ^= x >> 17; x ^= x << 5; x as u8 }).collect();
+        let mut mixed = vec![];
+        for i in 0..20 {
+            mixed.extend_from_slice(if i % 2 == 0 { &text[..50000] } else { &noise[..30000] });
+        }
+        let mut inputs: Vec<Vec<u8>> = [0, 1, 16, 17, 65535, 65536, 65537, 65536 * 3 + 10]
+            .iter().map(|&n| text.iter().cycle().take(n).cloned().collect()).collect();
+        inputs.push(noise);
+        inputs.push(mixed);
+        inputs
+    }
+
+    fn test_encode_parallel() {
+        for src in inputs().iter() {
+            let want = compress_to_vec(src).unwrap();
+            for &threads in [0, 1, 2, 3, 8].iter() {
+                assert_eq!(encode_parallel(src, threads).unwrap(), want, "len {} threads {}", src.len(), threads);
+            }
+        }
+    }
+
+    /// Write `src` in writes of `size` bytes, flushing after every `flush_every` writes.
+    fn write<W: Write>(mut w: W, src: &[u8], size: usize, flush_every: usize) -> W {
+        for (i, p) in src.chunks(size).enumerate() {
+            w.write_all(p).unwrap();
+            if (i + 1) % flush_every == 0 {
+                w.flush().unwrap();
+            }
+        }
+        w.flush().unwrap();
+        w
+    }
+
+    fn test_frame_encoder() {
+        for src in inputs().iter() {
+            for &(size, flush_every) in [(1 << 20, 1), (1000, 7), (70000, 1000)].iter() {
+                let want = write(FrameEncoder::new(vec![]), src, size, flush_every).into_inner().unwrap();
+                for &threads in [1, 3].iter() {
+                    let enc = write(ParallelFrameEncoder::new(vec![], threads), src, size, flush_every);
+                    assert_eq!(enc.into_inner().unwrap(), want);
+                }
+            }
+        }
+
+        // The buffered data is flushed on drop.
+        let mut dst = vec![];
+        {
+            let mut enc = ParallelFrameEncoder::new(&mut dst, 2);
+            enc.write_all(b"hello world! hello world!").unwrap();
+        }
+        assert_eq!(&dst[..10], b"\xff\x06\x00\x00sNaPpY");
+    }
+
+    /// A writer that fails after `n` bytes.
+    struct Failing(usize);
+
+    impl Write for Failing {
+        fn write(&mut self, p: &[u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken"))
+            }
+            let n = std::cmp::min(self.0, p.len());
+            self.0 -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn test_frame_encoder_error() {
+        let src = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt").repeat(20);
+        let mut enc = ParallelFrameEncoder::new(Failing(1000), 2);
+        let err = enc.write_all(&src).and_then(|_| enc.flush()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        // The error sticks.
+        assert_eq!(enc.write(b"more").unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    /// A reader that returns at most one byte per read.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, p: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || p.is_empty() {
+                return Ok(0)
+            }
+            p[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    /// Decode `src` with both decoders and check they agree, on the data or the error.
+    fn check_decode(src: &[u8], threads: usize, read_ahead: usize, options: DecodeOptions) -> io::Result<Vec<u8>> {
+        let mut want = vec![];
+        let want_ret = FrameDecoder::with_options(src, options).read_to_end(&mut want);
+        let mut dec = ParallelFrameDecoder::with_options(src, threads, read_ahead, options);
+        let mut got = vec![];
+        let ret = dec.read_to_end(&mut got);
+        assert_eq!(got, want);
+        match (&ret, &want_ret) {
+            (Ok(_), Ok(_)) => {}
+            (Err(a), Err(b)) => {
+                assert_eq!(a.to_string(), b.to_string());
+                assert!(a.get_ref().unwrap().downcast_ref::<SnappyError>().is_some());
+                assert_error_sticks(&mut dec, a);
+            }
+            _ => panic!("got {:?}, want {:?}", ret, want_ret)
+        }
+        ret.map(|_| got)
+    }
+
+    fn test_frame_decoder() {
+        let none = DecodeOptions::default();
+        for src in inputs().iter() {
+            let framed = write(FrameEncoder::new(vec![]), src, 10000, 3).into_inner().unwrap();
+            for &(threads, read_ahead) in [(1, 1), (2, 3), (4, 100)].iter() {
+                assert_eq!(&check_decode(&framed, threads, read_ahead, none).unwrap(), src);
+            }
+            let mut dst = vec![];
+            ParallelFrameDecoder::new(Trickle(&framed), 0).read_to_end(&mut dst).unwrap();
+            assert_eq!(&dst, src);
+        }
+
+        let mut framed = write(FrameEncoder::new(vec![]), b"line one\nline two\n", 9, 1).into_inner().unwrap();
+        // Padding and a reserved skippable chunk.
+        framed.extend_from_slice(&[0xfe, 3, 0, 0, 0, 0, 0]);
+        framed.extend_from_slice(&[0x80, 2, 0, 0, 1, 2]);
+        framed.extend_from_slice(&write(FrameEncoder::new(vec![]), b"line three\n", 100, 1).into_inner().unwrap());
+        let lines: Vec<String> = ParallelFrameDecoder::new(&framed[..], 2).lines().map(|l| l.unwrap()).collect();
+        assert_eq!(lines, vec!["line one", "line two", "line three"]);
+    }
+
+    fn test_frame_decoder_errors() {
+        let none = DecodeOptions::default();
+        let src = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt").repeat(20);
+        let framed = write(FrameEncoder::new(vec![]), &src, 1 << 20, 1).into_inner().unwrap();
+        // Flip bytes all over the stream, and cut it everywhere. Both decoders must agree.
+        for i in (0..framed.len()).step_by(997).chain(0..40) {
+            let mut corrupt = framed.clone();
+            corrupt[i] ^= 0x55;
+            let _ = check_decode(&corrupt, 3, 4, none);
+            let _ = check_decode(&framed[..i], 2, 2, none);
+        }
+        // A reserved unskippable chunk after the data.
+        let mut corrupt = framed.clone();
+        corrupt.extend_from_slice(&[0x02, 2, 0, 0, 1, 2]);
+        assert!(check_decode(&corrupt, 2, 8, none).is_err());
+
+        assert!(check_decode(&framed[..framed.len() - 1], 2, 2, none).is_err());
+
+        let options = DecodeOptions { max_decoded_len: Some(200000), ..DecodeOptions::default() };
+        let err = check_decode(&framed, 2, 8, options).unwrap_err();
+        assert_eq!(err.get_ref().unwrap().downcast_ref::<SnappyError>(), Some(&SnappyError::LimitExceeded));
+    }
+
+    #[test]
+    fn it_works() {
+        test_encode_parallel();
+        test_frame_encoder();
+        test_frame_encoder_error();
+        test_frame_decoder();
+        test_frame_decoder_errors();
+    }
+}
@@ -0,0 +1,194 @@
+#[cfg(test)]
+mod tests {
+    use xsnappy::s2;
+    use xsnappy::error::{SnappyError, CorruptKind, TagKind};
+    use xsnappy::{compress_to_vec, decompress_to_vec, max_encode_len, DecodeOptions};
+    use crate::golden::{read_file_to_vec, read_fixture};
+
+    /// Return a block of the length header of `n` followed by `tags`.
+    fn block(n: usize, tags: &[u8]) -> Vec<u8> {
+        let mut dst = vec![];
+        let mut x = n;
+        while x >= 0x80 {
+            dst.push(x as u8 | 0x80);
+            x >>= 7;
+        }
+        dst.push(x as u8);
+        dst.extend_from_slice(tags);
+        dst
+    }
+
+    fn corruption(err: SnappyError) -> (CorruptKind, usize, Option<TagKind>) {
+        match err {
+            SnappyError::Corrupt(c) => (c.kind, c.input_offset, c.tag),
+            e => panic!("unexpected error {:?}", e)
+        }
+    }
+
+    fn test_decode_repeats() {
+        // "abcd", a copy1 of offset 4, and a repeat of 4 bytes.
+        let src = block(12, &[0x0c, b'a', b'b', b'c', b'd', 0x01, 0x04, 0x01, 0x00]);
+        assert_eq!(s2::decompress_to_vec(&src).unwrap(), b"abcdabcdabcd");
+        // Snappy reads the repeat as a copy of offset 0.
+        assert_eq!(corruption(decompress_to_vec(&src).unwrap_err()).0, CorruptKind::ZeroOffset);
+
+        // "xyz", a copy2 of offset 3, and a repeat of its offset.
+        let src = block(13, &[0x08, b'x', b'y', b'z', 0x16, 0x03, 0x00, 0x01, 0x00]);
+        assert_eq!(s2::decompress_to_vec(&src).unwrap(), b"xyzxyzxyzxyzx");
+
+        // "a", a copy1 of offset 1, and repeats with lengths in 1, 2 and 3 more bytes.
+        for &(tag, ref len_bytes, len) in [(0x15, vec![2], 10), (0x19, vec![2, 1], 258 + 260),
+                                           (0x1d, vec![0, 0, 1], 65536 + 65540)].iter() {
+            let mut tags = vec![0x00, b'a', 0x01, 0x01, tag, 0x00];
+            tags.extend_from_slice(len_bytes);
+            let n = 1 + 4 + len;
+            let src = block(n, &tags);
+            assert_eq!(s2::decode_len(&src).unwrap(), n);
+            assert_eq!(s2::decompress_to_vec(&src).unwrap(), vec![b'a'; n]);
+            // Cut off inside the length.
+            let h = src.len() - tags.len();
+            let err = s2::decompress_to_vec(&src[..src.len() - 1]).unwrap_err();
+            assert_eq!(corruption(err), (CorruptKind::TruncatedTag, h + 4, Some(TagKind::Copy1)));
+        }
+
+        // A repeat before any copy.
+        let src = block(5, &[0x00, b'a', 0x01, 0x00]);
+        assert_eq!(corruption(s2::decompress_to_vec(&src).unwrap_err()), (CorruptKind::ZeroOffset, 3, Some(TagKind::Copy1)));
+        // A repeat past the decoded length.
+        let src = block(8, &[0x0c, b'a', b'b', b'c', b'd', 0x01, 0x04, 0x01, 0x00]);
+        assert_eq!(corruption(s2::decompress_to_vec(&src).unwrap_err()).0, CorruptKind::PastDecodedLen(4));
+        let mut dst = [0; 11];
+        assert_eq!(s2::decode(&mut dst, &block(12, &[])), Err(SnappyError::DstTooSmall));
+    }
+
+    /// Inputs of many sizes and kinds, including matches beyond 64 KiB.
+    fn inputs() -> Vec<Vec<u8>> {
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let mut x = 1u32;
+        let noise: Vec<u8> = (0..200000).map(|_| { x ^= x << 13; x ^= x >> 17; x ^= x << 5; x as u8 }).collect();
+        let mut far = noise[..100000].to_vec();
+        far.extend_from_slice(&noise[..100000]);
+        far.extend_from_slice(&text);
+        let mut inputs: Vec<Vec<u8>> = [0, 1, 31, 32, 33, 100, 65536, 200000]
+            .iter().map(|&n| text.iter().cycle().take(n).cloned().collect()).collect();
+        inputs.push(text.repeat(40));
+        inputs.push(noise);
+        inputs.push(far);
+        inputs.push(vec![0; 5 << 20]);
+        inputs.push(b"abc".repeat(30000));
+        inputs
+    }
+
+    fn test_round_trip() {
+        for src in inputs().iter() {
+            let compressed = s2::compress_to_vec(src).unwrap();
+            assert!(compressed.len() <= s2::max_encode_len(src.len()));
+            assert_eq!(&s2::decompress_to_vec(&compressed).unwrap(), src, "len {}", src.len());
+            // The S2 decoder reads every Snappy block.
+            assert_eq!(&s2::decompress_to_vec(&compress_to_vec(src).unwrap()).unwrap(), src);
+        }
+
+        // Long runs take a few bytes as repeats, where Snappy takes 3 bytes per 64.
+        let zeros = s2::compress_to_vec(&[0; 1 << 20]).unwrap();
+        assert!(zeros.len() < 32, "{}", zeros.len());
+        // Incompressible data is emitted as a single literal.
+        let noise = &inputs()[9];
+        assert_eq!(s2::compress_to_vec(noise).unwrap().len(), s2::max_encode_len(noise.len()));
+        assert!(s2::max_encode_len(noise.len()) < max_encode_len(noise.len()));
+
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt").repeat(4);
+        let mut dst = vec![0; s2::max_encode_len(text.len())];
+        let n = s2::encode(&mut dst, &text);
+        let mut decoded = vec![0; text.len()];
+        assert_eq!(s2::decode(&mut decoded, &dst[..n]), Ok(text.len()));
+        assert_eq!(decoded, text);
+    }
+
+    /// Blocks as `encodeBlockGo`, the encoder of the Go package without assembly,
+    /// emits them. They were traced by hand through its source, not produced by it.
+    fn test_golden() {
+        let a = b"0123456789abcdef";
+        let mut cut = a.repeat(2);
+        cut.push(b'#');
+        cut.extend_from_slice(a);
+        let mut golden = vec![
+            // The first match is a copy, even at the offset a repeat would take.
+            (vec![b'a'; 40], vec![0x28, 0x00, b'a', 0x92, 0x01, 0x00, 0x04, b'a', b'a']),
+            // A run of zeros, a copy1 of 8 bytes and a repeat for the rest of the match.
+            (vec![0; 100], vec![0x64, 0x00, 0x00, 0x11, 0x01, 0x15, 0x00, 0x4d, 0x14, 0, 0, 0, 0, 0, 0]),
+        ];
+        let mut out = vec![0x30, 0x3c];
+        out.extend_from_slice(a);
+        out.extend_from_slice(&[0x6e, 0x10, 0x00, 0x0c, b'c', b'd', b'e', b'f']);
+        golden.push((a.repeat(3), out));
+        let mut out = vec![0x31, 0x3c];
+        out.extend_from_slice(a);
+        out.extend_from_slice(&[0x3e, 0x10, 0x00, 0x00, b'#', 0x2e, 0x21, 0x00, 0x0c, b'c', b'd', b'e', b'f']);
+        golden.push((cut, out));
+
+        let mut scratch = Box::new(s2::EncodeScratch::new());
+        for (src, out) in golden.iter() {
+            assert_eq!(&s2::compress_to_vec(src).unwrap(), out);
+            assert_eq!(&s2::decompress_to_vec(out).unwrap(), src);
+            let mut dst = vec![0; s2::max_encode_len(src.len())];
+            assert_eq!(s2::encode_with_scratch(&mut dst, src, &mut scratch), Ok(out.len()));
+            assert_eq!(&dst[..out.len()], &out[..]);
+            assert_eq!(s2::encode_with_scratch(&mut dst[..out.len()], src, &mut scratch), Err(SnappyError::DstTooSmall));
+        }
+        // The scratch table gives the same output as `encode` on every input.
+        for src in inputs().iter() {
+            let mut dst = vec![0; s2::max_encode_len(src.len())];
+            let n = s2::encode_with_scratch(&mut dst, src, &mut scratch).unwrap();
+            assert_eq!(dst[..n], s2::compress_to_vec(src).unwrap()[..]);
+        }
+    }
+
+    /// A block written by the Go package, see `testdata/s2/gen`.
+    fn test_go_block() {
+        let block = match read_fixture("testdata/s2/block.s2") {
+            Some(block) => block,
+            None => return
+        };
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let mut src = text[..10000].to_vec();
+        src.extend_from_slice(&[0; 10000]);
+        src.extend_from_slice(&text[..10000]);
+        assert_eq!(s2::decompress_to_vec(&block).unwrap(), src);
+        // It holds repeats, which Snappy reads as copies of offset 0.
+        assert_eq!(corruption(decompress_to_vec(&block).unwrap_err()).0, CorruptKind::ZeroOffset);
+    }
+
+    fn test_limits() {
+        // A header claiming 4 GiB is rejected before anything is allocated.
+        let bomb = [0xff, 0xff, 0xff, 0xff, 0x0f, 0x00];
+        let options = DecodeOptions { max_decoded_len: Some(1 << 20), max_ratio: None };
+        assert_eq!(s2::decompress_to_vec_with_options(&bomb, &options), Err(SnappyError::LimitExceeded));
+        let mut dst = [0; 16];
+        assert_eq!(s2::decode_with_options(&mut dst, &bomb, &options), Err(SnappyError::LimitExceeded));
+
+        // Repeats make S2 blocks denser than Snappy's, which the ratio limit catches too.
+        let zeros = s2::compress_to_vec(&[0; 1 << 20]).unwrap();
+        let options = DecodeOptions { max_decoded_len: None, max_ratio: Some(1000) };
+        assert_eq!(s2::decompress_to_vec_with_options(&zeros, &options), Err(SnappyError::LimitExceeded));
+        let options = DecodeOptions { max_decoded_len: Some(1 << 20), max_ratio: None };
+        assert_eq!(s2::decompress_to_vec_with_options(&zeros, &options).unwrap(), vec![0; 1 << 20]);
+        let mut dst = vec![0; 1 << 20];
+        assert_eq!(s2::decode_with_options(&mut dst, &zeros, &options), Ok(1 << 20));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_dst_too_small() {
+        let mut dst = vec![0; 10];
+        s2::encode(&mut dst, b"hello world! hello world!");
+    }
+
+    #[test]
+    fn it_works() {
+        test_decode_repeats();
+        test_round_trip();
+        test_golden();
+        test_limits();
+        test_go_block();
+    }
+}
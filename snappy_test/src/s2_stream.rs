@@ -0,0 +1,279 @@
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+    use xsnappy::s2::{S2Reader, S2Writer, WriterOptions, MIN_BLOCK_SIZE};
+    use xsnappy::error::{SnappyError, CorruptKind};
+    use xsnappy::{FrameEncoder, FrameDecoder};
+    use crate::golden::{read_file_to_vec, read_fixture};
+    use crate::stream_util::{assert_error_sticks, corruption};
+
+    const S2_MAGIC_CHUNK: &[u8] = b"\xff\x06\x00\x00S2sTwO";
+
+    fn write(options: WriterOptions, src: &[u8], size: usize) -> Vec<u8> {
+        let mut w = S2Writer::with_options(vec![], options);
+        for p in src.chunks(size) {
+            w.write_all(p).unwrap();
+        }
+        w.into_inner().unwrap()
+    }
+
+    fn read(src: &[u8]) -> io::Result<Vec<u8>> {
+        let mut dst = vec![];
+        S2Reader::new(src).read_to_end(&mut dst)?;
+        Ok(dst)
+    }
+
+    /// A reader that counts the bytes read from it.
+    struct CountingReader<R> {
+        r: R,
+        n: usize,
+    }
+
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, p: &mut [u8]) -> io::Result<usize> {
+            let n = self.r.read(p)?;
+            self.n += n;
+            Ok(n)
+        }
+    }
+
+    impl<R: Seek> Seek for CountingReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.r.seek(pos)
+        }
+    }
+
+    /// 8 MiB of text with a counter, so every offset has distinct data around it.
+    fn big_input() -> Vec<u8> {
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let mut src = vec![];
+        let mut k = 0u32;
+        while src.len() < 8 << 20 {
+            src.extend_from_slice(&k.to_le_bytes());
+            src.extend_from_slice(&text[k as usize % 10000..][..500]);
+            k += 1;
+        }
+        src.truncate(8 << 20);
+        src
+    }
+
+    fn test_round_trip() {
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let mut x = 1u32;
+        let noise: Vec<u8> = (0..100000).map(|_| { x ^= x << 13; x ^= x >> 17; x ^= x << 5; x as u8 }).collect();
+        for src in [&b""[..], &b"hello"[..], &text[..], &noise[..], &text.repeat(100)[..]].iter() {
+            for &(block_size, index) in [(1 << 20, true), (MIN_BLOCK_SIZE, true), (5000, false)].iter() {
+                for &size in [1000, 1 << 20].iter() {
+                    let stream = write(WriterOptions { block_size, index }, src, size);
+                    assert_eq!(&read(&stream).unwrap()[..], *src);
+                    if src.is_empty() {
+                        assert!(stream.is_empty());
+                        continue
+                    }
+                    assert_eq!(&stream[..S2_MAGIC_CHUNK.len()], S2_MAGIC_CHUNK);
+                    assert_eq!(stream.ends_with(b"\x00xdi2s"), index);
+                }
+            }
+        }
+
+        // Repetitive data takes far less than with Snappy chunks of 64 KiB.
+        let src = text.repeat(100);
+        let s2 = write(WriterOptions::default(), &src, 1 << 20);
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(&src).unwrap();
+        let snappy = enc.into_inner().unwrap();
+        assert!(s2.len() * 4 < snappy.len(), "{} vs {}", s2.len(), snappy.len());
+
+        // Flushing emits a chunk, and dropping the writer ends the stream with the index.
+        let mut stream = vec![];
+        {
+            let mut w = S2Writer::new(&mut stream);
+            w.write_all(b"hello").unwrap();
+            w.flush().unwrap();
+            assert_eq!(w.get_ref().len(), S2_MAGIC_CHUNK.len() + 4 + 4 + 5);
+        }
+        assert!(stream.ends_with(b"\x00xdi2s"));
+        assert_eq!(read(&stream).unwrap(), b"hello");
+    }
+
+    fn test_snappy_compat() {
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt").repeat(10);
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(&text).unwrap();
+        let framed = enc.into_inner().unwrap();
+        assert_eq!(read(&framed).unwrap(), text);
+
+        // Concatenated streams of both kinds read as one.
+        let mut both = write(WriterOptions::default(), &text, 4096);
+        both.extend_from_slice(&framed);
+        let mut r = S2Reader::new(Cursor::new(&both));
+        let mut dst = vec![];
+        r.read_to_end(&mut dst).unwrap();
+        assert_eq!(dst, text.repeat(2));
+        // The index of the first stream doesn't cover the second, so seeking decodes.
+        r.seek(SeekFrom::Start(text.len() as u64 + 10)).unwrap();
+        let mut dst = [0; 20];
+        r.read_exact(&mut dst).unwrap();
+        assert_eq!(dst, text[10..30]);
+        assert_eq!(r.seek(SeekFrom::End(-5)).unwrap(), 2 * text.len() as u64 - 5);
+
+        // Snappy readers don't read S2 streams.
+        let s2 = write(WriterOptions::default(), &text, 4096);
+        let err = FrameDecoder::new(&s2[..]).read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(corruption(&err), (CorruptKind::StreamIdentifier, 0));
+
+        // A Snappy stream may not have S2 chunks larger than 64 KiB.
+        let big = vec![7; 100000];
+        let stream = write(WriterOptions::default(), &big, big.len());
+        let mut snappy = stream.clone();
+        snappy[4..10].copy_from_slice(b"sNaPpY");
+        assert_eq!(read(&stream).unwrap(), big);
+        assert_eq!(corruption(&read(&snappy).unwrap_err()), (CorruptKind::ChunkLength, 10));
+    }
+
+    /// A stream with a seek index written by the Go package, see `testdata/s2/gen`.
+    fn test_go_stream() {
+        let stream = match read_fixture("testdata/s2/stream.s2") {
+            Some(stream) => stream,
+            None => return
+        };
+        let src = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt").repeat(400);
+        assert_eq!(&stream[..S2_MAGIC_CHUNK.len()], S2_MAGIC_CHUNK);
+        // The index chunk before the end, with its trailer.
+        assert!(stream.ends_with(b"\x00xdi2s"));
+        assert_eq!(read(&stream).unwrap(), src);
+
+        let mut r = S2Reader::new(Cursor::new(&stream));
+        let mut buf = vec![0; 3000];
+        for &pos in [3_000_000, 100, (1 << 20) - 1, 0, 4_444_444].iter() {
+            assert_eq!(r.seek(SeekFrom::Start(pos)).unwrap(), pos);
+            r.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf[..], &src[pos as usize..][..3000], "pos {}", pos);
+        }
+        assert_eq!(r.seek(SeekFrom::End(-10)).unwrap(), src.len() as u64 - 10);
+        let mut tail = vec![];
+        r.read_to_end(&mut tail).unwrap();
+        assert_eq!(&tail[..], &src[src.len() - 10..]);
+    }
+
+    fn test_seek() {
+        let src = big_input();
+        for &(block_size, index) in [(1 << 20, true), (100 << 10, true), (MIN_BLOCK_SIZE, true), (1 << 20, false)].iter() {
+            let stream = write(WriterOptions { block_size, index }, &src, 1 << 16);
+            let mut r = S2Reader::new(Cursor::new(&stream));
+            let mut buf = vec![0; 3000];
+            for &pos in [5_000_000, 100, 7 << 20, (8 << 20) - 3000, 1 << 20, (1 << 20) - 1, 0, 4_444_444].iter() {
+                assert_eq!(r.seek(SeekFrom::Start(pos)).unwrap(), pos);
+                r.read_exact(&mut buf).unwrap();
+                assert_eq!(&buf[..], &src[pos as usize..][..3000], "block size {}, pos {}", block_size, pos);
+                assert_eq!(r.seek(SeekFrom::Current(0)).unwrap(), pos + 3000);
+            }
+            assert_eq!(r.seek(SeekFrom::Current(-6000)).unwrap(), 4_444_444 - 3000);
+            r.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf[..], &src[4_444_444 - 3000..][..3000]);
+            assert_eq!(r.seek(SeekFrom::End(-10)).unwrap(), src.len() as u64 - 10);
+            let mut tail = vec![];
+            r.read_to_end(&mut tail).unwrap();
+            assert_eq!(&tail[..], &src[src.len() - 10..]);
+            assert_eq!(r.seek(SeekFrom::End(0)).unwrap(), src.len() as u64);
+
+            let err = r.seek(SeekFrom::Current(-(src.len() as i64) - 1)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            let err = r.seek(SeekFrom::End(1)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+            assert_eq!(r.seek(SeekFrom::Start(3)).unwrap(), 3);
+            r.read_exact(&mut buf[..4]).unwrap();
+            assert_eq!(&buf[..4], &src[3..7]);
+        }
+
+        // Without an index, a fresh reader seeks from the end to before the last chunk.
+        let src = &src[..3 << 20];
+        let mut enc = FrameEncoder::new(vec![]);
+        enc.write_all(src).unwrap();
+        let framed = enc.into_inner().unwrap();
+        for stream in [write(WriterOptions { block_size: 1 << 20, index: false }, src, 1 << 16), framed].iter() {
+            let mut r = S2Reader::new(Cursor::new(stream));
+            assert_eq!(r.seek(SeekFrom::End(-(2 << 20))).unwrap(), 1 << 20);
+            let mut buf = [0; 100];
+            r.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf[..], &src[1 << 20..][..100]);
+        }
+        let src = big_input();
+
+        // With the index, a seek reads the index and the chunks up to the target.
+        let stream = write(WriterOptions::default(), &src, 1 << 16);
+        let mut r = S2Reader::new(CountingReader { r: Cursor::new(&stream), n: 0 });
+        r.seek(SeekFrom::Start(7 << 20)).unwrap();
+        let mut buf = [0; 100];
+        r.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &src[7 << 20..][..100]);
+        assert!(r.get_ref().n < stream.len() / 6, "{} of {}", r.get_ref().n, stream.len());
+
+        // The stream may start inside the underlying reader.
+        let mut prefixed = b"some header".to_vec();
+        prefixed.extend_from_slice(&stream);
+        let mut c = Cursor::new(&prefixed);
+        c.seek(SeekFrom::Start(11)).unwrap();
+        let mut r = S2Reader::new(c);
+        r.read_exact(&mut buf).unwrap();
+        r.seek(SeekFrom::Start(6_000_000)).unwrap();
+        r.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &src[6_000_000..][..100]);
+    }
+
+    fn test_corrupt() {
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let stream = write(WriterOptions { block_size: MIN_BLOCK_SIZE, index: true }, &text, text.len());
+        let first_len = stream[11] as usize | (stream[12] as usize) << 8;
+        let second = S2_MAGIC_CHUNK.len() + 4 + first_len;
+
+        // A damaged second chunk fails at its offset, after the first is read.
+        let mut bad = stream.clone();
+        bad[second + 20] ^= 0x10;
+        let mut r = S2Reader::new(&bad[..]);
+        let mut dst = vec![0; MIN_BLOCK_SIZE];
+        r.read_exact(&mut dst).unwrap();
+        let err = r.read(&mut dst).unwrap_err();
+        let (kind, offset) = corruption(&err);
+        assert!(offset >= second, "{:?} at {}", kind, offset);
+        assert_error_sticks(&mut r, &err);
+
+        let err = read(&stream[..second + 5]).unwrap_err();
+        assert_eq!(corruption(&err), (CorruptKind::TruncatedChunk, second));
+        let err = read(&stream[S2_MAGIC_CHUNK.len()..]).unwrap_err();
+        assert_eq!(corruption(&err), (CorruptKind::StreamIdentifier, 0));
+        let mut bad = stream.clone();
+        bad[9] = b'o';
+        assert_eq!(corruption(&read(&bad).unwrap_err()), (CorruptKind::StreamIdentifier, 0));
+
+        // A reserved unskippable chunk fails, a skippable one is skipped.
+        let mut bad = S2_MAGIC_CHUNK.to_vec();
+        bad.extend_from_slice(&[0x02, 1, 0, 0, 0]);
+        match *read(&bad).unwrap_err().get_ref().unwrap().downcast_ref::<SnappyError>().unwrap() {
+            SnappyError::UnskippableChunk => {}
+            e => panic!("unexpected error {:?}", e)
+        }
+        let mut skippable = S2_MAGIC_CHUNK.to_vec();
+        skippable.extend_from_slice(&[0x80, 2, 0, 0, 0, 0]);
+        skippable.extend_from_slice(&stream[S2_MAGIC_CHUNK.len()..]);
+        assert_eq!(read(&skippable).unwrap(), text);
+
+        // A damaged index is ignored, and seeking decodes from the start.
+        let mut bad = stream.clone();
+        let n = bad.len();
+        bad[n - 15] ^= 0xff;
+        let mut r = S2Reader::new(Cursor::new(&bad));
+        r.seek(SeekFrom::Start(10000)).unwrap();
+        r.read_exact(&mut dst[..100]).unwrap();
+        assert_eq!(&dst[..100], &text[10000..10100]);
+    }
+
+    #[test]
+    fn it_works() {
+        test_round_trip();
+        test_snappy_compat();
+        test_seek();
+        test_go_stream();
+        test_corrupt();
+    }
+}
@@ -0,0 +1,64 @@
+#[cfg(test)]
+mod tests {
+    use xsnappy::{encode, encode_with_scratch, max_encode_len, decompress_to_vec, EncodeScratch};
+    use xsnappy::error::SnappyError;
+    use crate::golden::read_file_to_vec;
+
+    fn test_same_as_encode() {
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let mut scratch: Box<EncodeScratch> = Box::new(EncodeScratch::new());
+        // The scratch table is reused, from longer inputs to shorter ones.
+        for &n in [text.len(), 5000, 300, 17, 16, 1, 0].iter() {
+            let src = &text[..n];
+            let mut want = vec![0; max_encode_len(n)];
+            let len = encode(&mut want, src);
+            want.truncate(len);
+            let mut dst = vec![0; max_encode_len(n)];
+            let len = encode_with_scratch(&mut dst, src, &mut scratch).unwrap();
+            assert_eq!(&dst[..len], &want[..]);
+        }
+    }
+
+    /// Encode with a table of `N` entries and check the data decodes back.
+    fn encode_small<const N: usize>(src: &[u8]) -> usize {
+        let mut scratch = EncodeScratch::<N>::new();
+        let mut dst = vec![0; max_encode_len(src.len())];
+        let len = encode_with_scratch(&mut dst, src, &mut scratch).unwrap();
+        assert_eq!(decompress_to_vec(&dst[..len]).unwrap(), src);
+        len
+    }
+
+    fn test_table_sizes() {
+        assert_eq!(std::mem::size_of::<EncodeScratch<2048>>(), 4096);
+        assert_eq!(std::mem::size_of::<EncodeScratch>(), 32768);
+
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt").repeat(4);
+        let mut want = vec![0; max_encode_len(text.len())];
+        let want_len = encode(&mut want, &text);
+        assert_eq!(encode_small::<16384>(&text), want_len);
+        // A smaller table compresses worse, but the output stays valid.
+        let lens = [encode_small::<8192>(&text), encode_small::<2048>(&text), encode_small::<256>(&text)];
+        assert!(want_len <= lens[0] && lens[0] <= lens[1] && lens[1] <= lens[2], "{} {:?}", want_len, lens);
+        for &n in [0, 1, 17, 300, 5000].iter() {
+            encode_small::<256>(&text[..n]);
+            encode_small::<1024>(&text[..n]);
+        }
+    }
+
+    fn test_dst_too_small() {
+        let mut scratch: EncodeScratch = EncodeScratch::default();
+        let mut want = vec![0; max_encode_len(100)];
+        let len = encode(&mut want, &[7; 100]);
+        let mut dst = [0; 10];
+        assert_eq!(encode_with_scratch(&mut dst, &[7; 100], &mut scratch), Ok(len));
+        assert_eq!(&dst[..len], &want[..len]);
+        assert_eq!(encode_with_scratch(&mut dst, b"0123456789abcdef", &mut scratch), Err(SnappyError::DstTooSmall));
+    }
+
+    #[test]
+    fn it_works() {
+        test_same_as_encode();
+        test_table_sizes();
+        test_dst_too_small();
+    }
+}
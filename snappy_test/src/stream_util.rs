@@ -0,0 +1,29 @@
+//! Helpers shared by the tests of the stream readers and writers.
+
+use std::io::{self, Read};
+use xsnappy::error::{SnappyError, CorruptKind};
+
+/// Return the `SnappyError` that `err` carries.
+pub fn snappy_error(err: &io::Error) -> SnappyError {
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    *err.get_ref().unwrap().downcast_ref::<SnappyError>().unwrap()
+}
+
+/// Return the kind and the input offset of the corruption that `err` carries.
+pub fn corruption(err: &io::Error) -> (CorruptKind, usize) {
+    match snappy_error(err) {
+        SnappyError::Corrupt(c) => (c.kind, c.input_offset),
+        e => panic!("unexpected error {:?}", e)
+    }
+}
+
+/// Append `body` after its big-endian 32-bit length, as the Hadoop and xerial formats do.
+pub fn put_be_chunk(dst: &mut Vec<u8>, body: &[u8]) {
+    dst.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    dst.extend_from_slice(body);
+}
+
+/// Check that `r`, which failed with `err`, returns the same error on the next read.
+pub fn assert_error_sticks<R: Read>(r: &mut R, err: &io::Error) {
+    assert_eq!(r.read(&mut [0; 10]).unwrap_err().to_string(), err.to_string());
+}
@@ -0,0 +1,56 @@
+#[cfg(test)]
+mod tests {
+    use xsnappy::{compress_to_vec, decompress_to_vec, compress_into, decompress_into};
+    use xsnappy::{max_encode_len, encode};
+    use xsnappy::error::SnappyError;
+    use crate::golden::read_file_to_vec;
+
+    fn test_to_vec() {
+        let src = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let mut want = vec![0; max_encode_len(src.len())];
+        let n = encode(&mut want, &src);
+        want.truncate(n);
+
+        let compressed = compress_to_vec(&src).unwrap();
+        assert_eq!(compressed, want);
+        assert_eq!(decompress_to_vec(&compressed).unwrap(), src);
+
+        assert_eq!(compress_to_vec(b"").unwrap(), vec![0]);
+        assert!(decompress_to_vec(&[0]).unwrap().is_empty());
+    }
+
+    fn test_into() {
+        let src = b"You know some birds are not meant to be caged, \
+                    their feathers are just too bright.";
+        let mut compressed = b"prefix".to_vec();
+        let n = compress_into(&mut compressed, src).unwrap();
+        assert_eq!(compressed.len(), 6 + n);
+        assert_eq!(&compressed[..6], b"prefix");
+        assert_eq!(compressed[6..].to_vec(), compress_to_vec(src).unwrap());
+
+        let mut dst = b"prefix".to_vec();
+        let n = decompress_into(&mut dst, &compressed[6..]).unwrap();
+        assert_eq!(n, src.len());
+        assert_eq!(&dst[..6], b"prefix");
+        assert_eq!(&dst[6..], &src[..]);
+    }
+
+    fn test_corrupt() {
+        let mut compressed = compress_to_vec(&[7; 1000]).unwrap();
+        compressed.truncate(compressed.len() - 1);
+        let mut dst = b"prefix".to_vec();
+        match decompress_into(&mut dst, &compressed) {
+            Err(SnappyError::Corrupt(_)) => {}
+            _ => unreachable!()
+        }
+        assert_eq!(dst, b"prefix");
+        assert!(decompress_to_vec(&[0xff]).is_err());
+    }
+
+    #[test]
+    fn it_works() {
+        test_to_vec();
+        test_into();
+        test_corrupt();
+    }
+}
@@ -0,0 +1,142 @@
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+    use xsnappy::xerial::{XerialReader, XerialWriter, MAGIC};
+    use xsnappy::error::{SnappyError, CorruptKind};
+    use xsnappy::{compress_to_vec, DecodeOptions};
+    use crate::golden::read_file_to_vec;
+    use crate::stream_util::{assert_error_sticks, corruption, put_be_chunk as put_chunk, snappy_error};
+
+    const HEADER: &[u8] = b"\x82SNAPPY\x00\x00\x00\x00\x01\x00\x00\x00\x01";
+
+    fn write(block_size: usize, src: &[u8], size: usize) -> Vec<u8> {
+        let mut w = XerialWriter::with_block_size(vec![], block_size);
+        for p in src.chunks(size) {
+            w.write_all(p).unwrap();
+        }
+        w.into_inner().unwrap()
+    }
+
+    fn read(src: &[u8]) -> io::Result<Vec<u8>> {
+        let mut dst = vec![];
+        XerialReader::new(src).read_to_end(&mut dst)?;
+        Ok(dst)
+    }
+
+    fn test_round_trip() {
+        let text = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt").repeat(10);
+        for &(block_size, size) in [(32 * 1024, 1 << 20), (32 * 1024, 1000), (1024, 333), (1 << 20, 70000)].iter() {
+            let stream = write(block_size, &text, size);
+            assert_eq!(&stream[..16], HEADER);
+            assert_eq!(read(&stream).unwrap(), text);
+            // Every chunk holds a whole block but the last.
+            let len = u32::from_be_bytes([stream[16], stream[17], stream[18], stream[19]]) as usize;
+            let first = compress_to_vec(&text[..std::cmp::min(block_size, text.len())]).unwrap();
+            assert_eq!(&stream[20..20 + len], &first[..]);
+        }
+        // An empty stream is the header alone.
+        assert_eq!(write(1024, b"", 1), HEADER);
+        assert_eq!(read(HEADER).unwrap(), b"");
+        assert_eq!(read(b"").unwrap(), b"");
+    }
+
+    fn test_streams() {
+        // Data without the magic is a single raw block, even if shorter than the header.
+        for src in [&b"hi"[..], &b"hello world! hello world! hello world!"[..]].iter() {
+            assert_eq!(read(&compress_to_vec(src).unwrap()).unwrap(), *src);
+        }
+
+        // Concatenated streams, as Kafka appends batches.
+        let mut stream = write(1024, b"hello ", 1);
+        stream.extend_from_slice(&write(1024, b"world", 1));
+        stream.extend_from_slice(HEADER);
+        put_chunk(&mut stream, &compress_to_vec(b"!").unwrap());
+        assert_eq!(read(&stream).unwrap(), b"hello world!");
+
+        // Newer versions that stay compatible are read, incompatible ones are not.
+        let mut stream = MAGIC.to_vec();
+        stream.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 1]);
+        put_chunk(&mut stream, &compress_to_vec(b"hello").unwrap());
+        assert_eq!(read(&stream).unwrap(), b"hello");
+        stream[15] = 2;
+        assert_eq!(snappy_error(&read(&stream).unwrap_err()), SnappyError::Unsupported);
+    }
+
+    fn test_corrupt() {
+        let a = compress_to_vec(b"hello ").unwrap();
+        let mut stream = HEADER.to_vec();
+        put_chunk(&mut stream, &a);
+        put_chunk(&mut stream, &a);
+        // Cut off inside the chunks, but not between them.
+        for i in 17..stream.len() {
+            if i == 20 + a.len() {
+                continue
+            }
+            let err = read(&stream[..i]).unwrap_err();
+            assert_eq!(corruption(&err).0, CorruptKind::TruncatedChunk, "cut at {}", i);
+        }
+        assert_eq!(read(&stream[..20 + a.len()]).unwrap(), b"hello ");
+
+        // A negative length, and a concatenated header cut off or wrong.
+        let mut stream = HEADER.to_vec();
+        put_chunk(&mut stream, &a);
+        let n = stream.len();
+        stream.extend_from_slice(&[0xff, 0xff, 0xff, 0xf0]);
+        assert_eq!(corruption(&read(&stream).unwrap_err()), (CorruptKind::ChunkLength, n));
+        stream.truncate(n);
+        stream.extend_from_slice(&HEADER[..10]);
+        assert_eq!(corruption(&read(&stream).unwrap_err()), (CorruptKind::TruncatedChunk, n));
+        stream.truncate(n);
+        stream.extend_from_slice(b"\x82SNAxxxx\x00\x00\x00\x01\x00\x00\x00\x01");
+        assert_eq!(corruption(&read(&stream).unwrap_err()), (CorruptKind::StreamIdentifier, n));
+
+        // A corrupt Snappy block, at its offset in the stream. The error sticks.
+        let mut stream = HEADER.to_vec();
+        put_chunk(&mut stream, &a);
+        put_chunk(&mut stream, &[6, 0x10, b'x']);
+        let mut r = XerialReader::new(&stream[..]);
+        let mut dst = vec![];
+        let err = r.read_to_end(&mut dst).unwrap_err();
+        assert_eq!(dst, b"hello ");
+        match snappy_error(&err) {
+            SnappyError::Corrupt(c) => {
+                assert_eq!(c.kind, CorruptKind::LiteralPastInput(5));
+                assert_eq!((c.input_offset, c.output_offset), (20 + a.len() + 4 + 1, 6));
+            }
+            e => panic!("unexpected error {:?}", e)
+        }
+        assert_error_sticks(&mut r, &err);
+        // A raw block that is corrupt.
+        assert!(matches!(snappy_error(&read(&[6, 0x10, b'x']).unwrap_err()), SnappyError::Corrupt(_)));
+
+        // A block claiming more data than it can hold fails before the data is allocated.
+        let huge = [0xff, 0xff, 0xff, 0xff, 0x0f, 0xfe, 0, 0, 0xfe, 0];
+        let mut stream = HEADER.to_vec();
+        put_chunk(&mut stream, &huge);
+        assert_eq!(corruption(&read(&stream).unwrap_err()), (CorruptKind::ChunkLength, 20));
+        assert_eq!(corruption(&read(&huge).unwrap_err()), (CorruptKind::ChunkLength, 0));
+        // The densest blocks still decode.
+        let zeros = compress_to_vec(&[0; 1 << 20]).unwrap();
+        assert_eq!(read(&zeros).unwrap(), vec![0; 1 << 20]);
+
+        // The limits of `DecodeOptions`, on the whole stream and on every chunk.
+        let src = read_file_to_vec("testdata/Mark.Twain-Tom.Sawyer.txt");
+        let stream = write(4096, &src, 1 << 20);
+        let options = DecodeOptions { max_decoded_len: Some(10000), ..DecodeOptions::default() };
+        let mut dst = vec![];
+        let err = XerialReader::with_options(&stream[..], options).read_to_end(&mut dst).unwrap_err();
+        assert_eq!(snappy_error(&err), SnappyError::LimitExceeded);
+        assert_eq!(dst.len(), 8192);
+        let zeros = write(4096, &[0; 10000], 1 << 20);
+        let options = DecodeOptions { max_ratio: Some(10), ..DecodeOptions::default() };
+        let err = XerialReader::with_options(&zeros[..], options).read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(snappy_error(&err), SnappyError::LimitExceeded);
+    }
+
+    #[test]
+    fn it_works() {
+        test_round_trip();
+        test_streams();
+        test_corrupt();
+    }
+}
@@ -0,0 +1,47 @@
+// Command gen writes the S2 fixtures of the snappy_test crate with the Go
+// package github.com/klauspost/compress/s2, to check that xsnappy reads what
+// it writes. Run it in this directory:
+//
+//	go mod tidy && go run .
+//
+// It writes ../block.s2 and ../stream.s2 from ../../Mark.Twain-Tom.Sawyer.txt.
+package main
+
+import (
+	"bytes"
+	"log"
+	"os"
+
+	"github.com/klauspost/compress/s2"
+)
+
+func main() {
+	text, err := os.ReadFile("../../Mark.Twain-Tom.Sawyer.txt")
+	if err != nil {
+		log.Fatal(err)
+	}
+
+	// A block of text, a run of zeros and the text again. The run is emitted
+	// as a copy followed by repeats, which Snappy decoders reject.
+	var src []byte
+	src = append(src, text[:10000]...)
+	src = append(src, make([]byte, 10000)...)
+	src = append(src, text[:10000]...)
+	if err := os.WriteFile("../block.s2", s2.Encode(nil, src), 0o644); err != nil {
+		log.Fatal(err)
+	}
+
+	// A stream of 400 copies of the text in blocks of 256 KiB, ending with
+	// the seek index chunk (0x99).
+	var buf bytes.Buffer
+	w := s2.NewWriter(&buf, s2.WriterBlockSize(256<<10), s2.WriterConcurrency(1), s2.WriterAddIndex())
+	if _, err := w.Write(bytes.Repeat(text, 400)); err != nil {
+		log.Fatal(err)
+	}
+	if err := w.Close(); err != nil {
+		log.Fatal(err)
+	}
+	if err := os.WriteFile("../stream.s2", buf.Bytes(), 0o644); err != nil {
+		log.Fatal(err)
+	}
+}
@@ -0,0 +1,141 @@
+//! `xsnappy inspect`: explain the structure of compressed data.
+
+use crate::Format;
+use std::io::{self, Write};
+use xsnappy::error::TagKind;
+use xsnappy::{chunks, ops, ChunkType, Op};
+
+/// Return the format of `src`: framed if it starts with a stream identifier.
+pub fn detect(src: &[u8]) -> Format {
+    match chunks(src).next() {
+        Some(Ok(chunk)) if chunk.chunk_type == ChunkType::StreamIdentifier => Format::Framed,
+        _ => Format::Raw
+    }
+}
+
+/// Write a report on `src` to `out`. Return false if `src` is corrupt.
+pub fn inspect<W: Write>(out: &mut W, src: &[u8], format: Format) -> io::Result<bool> {
+    match format {
+        Format::Raw => inspect_raw(out, src),
+        Format::Framed => inspect_framed(out, src),
+    }
+}
+
+/// A histogram with power-of-two buckets: bucket `k` counts the values in `2^k..2^(k+1)`.
+struct Histogram([u64; 64]);
+
+impl Histogram {
+    fn add(&mut self, v: usize) {
+        self.0[63 - (v as u64).leading_zeros() as usize] += 1;
+    }
+
+    fn write<W: Write>(&self, out: &mut W, title: &str) -> io::Result<()> {
+        writeln!(out, "{}:", title)?;
+        for (k, &n) in self.0.iter().enumerate().filter(|&(_, &n)| n > 0) {
+            let (lo, hi) = (1u64 << k, (1u64 << k) * 2 - 1);
+            let range = if lo == hi { lo.to_string() } else { format!("{}-{}", lo, hi) };
+            writeln!(out, "  {:<22}{:>12}", range, n)?;
+        }
+        Ok(())
+    }
+}
+
+fn inspect_raw<W: Write>(out: &mut W, src: &[u8]) -> io::Result<bool> {
+    writeln!(out, "format: raw")?;
+    let header_len = src.iter().take(10).position(|&b| b < 0x80).map_or(0, |i| i + 1);
+    let header: Vec<String> = src[..header_len].iter().map(|b| format!("{:02x}", b)).collect();
+    writeln!(out, "header: {} ({} bytes)", header.join(" "), header_len)?;
+    let it = match ops(src) {
+        Ok(it) => it,
+        Err(err) => {
+            writeln!(out, "error: {}", err)?;
+            return Ok(false)
+        }
+    };
+    writeln!(out, "decoded length: {}", it.decoded_len())?;
+    writeln!(out, "encoded length: {}", src.len())?;
+
+    let names = ["literal", "copy1", "copy2", "copy4"];
+    let mut counts = [0u64; 4];
+    let mut bytes = [0u64; 4];
+    let mut lengths = Histogram([0; 64]);
+    let mut offsets = Histogram([0; 64]);
+    let mut error = None;
+    for op in it {
+        let op = match op {
+            Ok((_, op)) => op,
+            Err(err) => {
+                error = Some(err);
+                break
+            }
+        };
+        let i = match op {
+            Op::Literal { .. } => 0,
+            Op::Copy { offset, len, tag } => {
+                lengths.add(len);
+                offsets.add(offset);
+                match tag {
+                    TagKind::Copy1 => 1,
+                    TagKind::Copy2 => 2,
+                    _ => 3,
+                }
+            }
+        };
+        counts[i] += 1;
+        bytes[i] += op.output_len() as u64;
+    }
+
+    writeln!(out, "{:<24}{:>12}{:>16}", "ops:", "count", "decoded bytes")?;
+    for i in 0..4 {
+        writeln!(out, "  {:<22}{:>12}{:>16}", names[i], counts[i], bytes[i])?;
+    }
+    lengths.write(out, "copy lengths")?;
+    offsets.write(out, "copy offsets")?;
+    match error {
+        Some(err) => {
+            writeln!(out, "error: {}", err)?;
+            Ok(false)
+        }
+        None => Ok(true)
+    }
+}
+
+fn inspect_framed<W: Write>(out: &mut W, src: &[u8]) -> io::Result<bool> {
+    writeln!(out, "format: framed")?;
+    writeln!(out, "{:>12}  {:<20}{:>10}{:>10}  status", "offset", "type", "length", "decoded")?;
+    // A checksum mismatch is reported on its chunk, only structural errors stop the listing.
+    let mut it = chunks(src).ignore_checksums();
+    let (mut count, mut decoded_total, mut mismatches) = (0, 0, 0);
+    let mut error = None;
+    for chunk in &mut it {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                error = Some(err);
+                break
+            }
+        };
+        count += 1;
+        let crc = if chunk.crc_ok { "crc ok" } else { "crc mismatch" };
+        if !chunk.crc_ok {
+            mismatches += 1;
+        }
+        let (name, decoded, status) = match chunk.chunk_type {
+            ChunkType::Compressed => ("compressed", chunk.decoded_len.to_string(), crc),
+            ChunkType::Uncompressed => ("uncompressed", chunk.decoded_len.to_string(), crc),
+            ChunkType::StreamIdentifier => ("stream identifier", "-".to_string(), "ok"),
+            ChunkType::Padding => ("padding", "-".to_string(), "skipped"),
+            ChunkType::Skippable(_) => ("reserved skippable", "-".to_string(), "skipped"),
+        };
+        writeln!(out, "{:>12}  {:<20}{:>10}{:>10}  {}", chunk.offset, name, chunk.body.len(), decoded, status)?;
+        decoded_total += chunk.decoded_len;
+    }
+    if let Some(err) = &error {
+        writeln!(out, "{:>12}  error: {}", it.input_offset(), err)?;
+    }
+    writeln!(out, "chunks: {}, encoded length: {}, decoded length: {}", count, src.len(), decoded_total)?;
+    if mismatches > 0 {
+        writeln!(out, "checksum mismatches: {}", mismatches)?;
+    }
+    Ok(error.is_none() && mismatches == 0)
+}
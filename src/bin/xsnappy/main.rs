@@ -0,0 +1,312 @@
+//! `xsnappy`: compress, decompress and inspect files in the Snappy format.
+//!
+//! The options follow gzip: files are replaced by their compressed or
+//! decompressed versions unless `-k` or `-c` is given, and no input
+//! or `-` means stdin to stdout.
+
+mod inspect;
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, IsTerminal, Read, Write};
+use std::process;
+use xsnappy::{compress_to_vec, decompress_to_vec, FrameDecoder, FrameEncoder};
+
+const USAGE: &str = "\
+Usage: xsnappy <compress|decompress> [OPTIONS] [FILE]...
+       xsnappy inspect [--format FORMAT] [FILE]...
+
+With no FILE, or when FILE is -, read standard input and write standard output.
+
+inspect explains compressed data: the operations of a raw block, or the chunks
+of a framed stream. The format is detected unless --format is given.
+
+Options:
+  -c, --stdout          write to standard output and keep the input files
+  -k, --keep            keep the input files
+  -f, --force           overwrite output files, and write compressed data to a terminal
+      --format FORMAT   raw or framed [default: framed]
+  -h, --help            print this help";
+
+/// What to do with the files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    /// Compress or decompress them.
+    Transform(Mode),
+    /// Print a report on them.
+    Inspect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Compress,
+    Decompress,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// A single Snappy block.
+    Raw,
+    /// The Snappy framing format.
+    Framed,
+}
+
+impl Format {
+    /// Return the file name suffix of compressed files.
+    fn suffix(self) -> &'static str {
+        match self {
+            Format::Raw => ".snappy",
+            Format::Framed => ".sz",
+        }
+    }
+}
+
+struct Options {
+    command: Command,
+    /// The format of `--format`, if given.
+    format: Option<Format>,
+    stdout: bool,
+    keep: bool,
+    force: bool,
+    files: Vec<String>,
+}
+
+/// Why a file failed. Like gzip, errors exit with 1 and warnings with 2.
+enum Failure {
+    Error(String),
+    Warning(String),
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Options>, String> {
+    let command = match args.next().as_deref() {
+        Some("compress") => Command::Transform(Mode::Compress),
+        Some("decompress") => Command::Transform(Mode::Decompress),
+        Some("inspect") => Command::Inspect,
+        Some("-h") | Some("--help") => return Ok(None),
+        Some(cmd) => return Err(format!("unknown command '{}'", cmd)),
+        None => return Err("missing command".to_string()),
+    };
+    let mut opts = Options {
+        command,
+        format: None,
+        stdout: false,
+        keep: false,
+        force: false,
+        files: vec![],
+    };
+    let mut only_files = false;
+    while let Some(arg) = args.next() {
+        if only_files || arg == "-" || !arg.starts_with('-') {
+            opts.files.push(arg);
+            continue;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = match long.find('=') {
+                Some(i) => (&long[..i], Some(long[i + 1..].to_string())),
+                None => (long, None),
+            };
+            match name {
+                "" => only_files = true,
+                "stdout" | "to-stdout" => opts.stdout = true,
+                "keep" => opts.keep = true,
+                "force" => opts.force = true,
+                "help" => return Ok(None),
+                "format" => {
+                    let value = match value.or_else(|| args.next()) {
+                        Some(value) => value,
+                        None => return Err("missing value for --format".to_string()),
+                    };
+                    opts.format = match value.as_str() {
+                        "raw" => Some(Format::Raw),
+                        "framed" => Some(Format::Framed),
+                        _ => return Err(format!("unknown format '{}', expected raw or framed", value)),
+                    };
+                }
+                _ => return Err(format!("unknown option '{}'", arg)),
+            }
+            continue;
+        }
+        // Short options may be combined, as in -kf.
+        for c in arg[1..].chars() {
+            match c {
+                'c' => opts.stdout = true,
+                'k' => opts.keep = true,
+                'f' => opts.force = true,
+                'h' => return Ok(None),
+                _ => return Err(format!("unknown option '-{}'", c)),
+            }
+        }
+    }
+    if command == Command::Inspect && (opts.stdout || opts.keep || opts.force) {
+        return Err("inspect takes no -c, -k or -f".to_string());
+    }
+    Ok(Some(opts))
+}
+
+/// Compress or decompress `r` to `w`.
+fn transform<R: Read, W: Write>(opts: &Options, mode: Mode, mut r: R, mut w: W) -> io::Result<()> {
+    match (mode, opts.format.unwrap_or(Format::Framed)) {
+        (Mode::Compress, Format::Framed) => {
+            let mut enc = FrameEncoder::new(w);
+            io::copy(&mut r, &mut enc)?;
+            enc.into_inner()?.flush()
+        }
+        (Mode::Decompress, Format::Framed) => {
+            io::copy(&mut FrameDecoder::new(r), &mut w)?;
+            w.flush()
+        }
+        (mode, Format::Raw) => {
+            let mut src = vec![];
+            r.read_to_end(&mut src)?;
+            let dst = match mode {
+                Mode::Compress => compress_to_vec(&src)?,
+                Mode::Decompress => decompress_to_vec(&src)?,
+            };
+            w.write_all(&dst)?;
+            w.flush()
+        }
+    }
+}
+
+/// Return the name of the output file of `name`.
+fn output_name(opts: &Options, mode: Mode, name: &str) -> Result<String, Failure> {
+    let suffix = opts.format.unwrap_or(Format::Framed).suffix();
+    match mode {
+        Mode::Compress => {
+            if name.ends_with(suffix) && !opts.force {
+                return Err(Failure::Warning(format!("{} already has {} suffix -- unchanged", name, suffix)));
+            }
+            Ok(format!("{}{}", name, suffix))
+        }
+        Mode::Decompress => match name.strip_suffix(suffix) {
+            Some(out) if !out.is_empty() => Ok(out.to_string()),
+            _ => Err(Failure::Warning(format!("{}: unknown suffix -- ignored", name))),
+        },
+    }
+}
+
+/// Return stdout unless compressed data would go to a terminal without `-f`.
+fn check_terminal(opts: &Options, mode: Mode) -> Result<io::Stdout, Failure> {
+    let stdout = io::stdout();
+    if mode == Mode::Compress && !opts.force && stdout.is_terminal() {
+        return Err(Failure::Error(
+            "compressed data not written to a terminal. Use -f to force compression.".to_string()));
+    }
+    Ok(stdout)
+}
+
+fn process_stdin(opts: &Options, mode: Mode) -> Result<(), Failure> {
+    let stdout = check_terminal(opts, mode)?;
+    transform(opts, mode, io::stdin().lock(), BufWriter::new(stdout.lock()))
+        .map_err(|err| Failure::Error(format!("stdin: {}", err)))
+}
+
+fn process_file(opts: &Options, mode: Mode, name: &str) -> Result<(), Failure> {
+    let error = |err: io::Error| Failure::Error(format!("{}: {}", name, err));
+    let meta = fs::metadata(name).map_err(error)?;
+    if !meta.is_file() {
+        return Err(Failure::Warning(format!("{}: not a regular file -- ignored", name)));
+    }
+    let input = BufReader::new(File::open(name).map_err(error)?);
+
+    if opts.stdout {
+        let stdout = check_terminal(opts, mode)?;
+        return transform(opts, mode, input, BufWriter::new(stdout.lock())).map_err(error);
+    }
+
+    let out_name = output_name(opts, mode, name)?;
+    // Without -f, create the output only if it doesn't exist, in one step with the check.
+    let created = if opts.force {
+        File::create(&out_name)
+    } else {
+        OpenOptions::new().write(true).create_new(true).open(&out_name)
+    };
+    let output = created.map_err(|err| match err.kind() {
+        io::ErrorKind::AlreadyExists => Failure::Warning(format!("{} already exists -- not overwritten", out_name)),
+        _ => Failure::Error(format!("{}: {}", out_name, err))
+    })?;
+    let mut w = BufWriter::new(output);
+    if let Err(err) = transform(opts, mode, input, &mut w) {
+        drop(w);
+        let _ = fs::remove_file(&out_name);
+        return Err(error(err));
+    }
+    let output = w.into_inner().map_err(|err| error(err.into_error()))?;
+    // Like gzip, the output keeps the permissions and the modification time of the input.
+    output.set_permissions(meta.permissions()).map_err(error)?;
+    if let Ok(mtime) = meta.modified() {
+        output.set_modified(mtime).map_err(error)?;
+    }
+    drop(output);
+    if !opts.keep {
+        fs::remove_file(name).map_err(error)?;
+    }
+    Ok(())
+}
+
+/// Print a report on the file `name`, or stdin if it is `-`.
+fn process_inspect(opts: &Options, name: &str) -> Result<(), Failure> {
+    let label = if name == "-" { "stdin" } else { name };
+    let error = |err: io::Error| Failure::Error(format!("{}: {}", label, err));
+    let src = if name == "-" {
+        let mut src = vec![];
+        io::stdin().lock().read_to_end(&mut src).map_err(error)?;
+        src
+    } else {
+        fs::read(name).map_err(error)?
+    };
+    let format = opts.format.unwrap_or_else(|| inspect::detect(&src));
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if opts.files.len() > 1 {
+        writeln!(out, "{}:", label).map_err(error)?;
+    }
+    let valid = inspect::inspect(&mut out, &src, format).and_then(|valid| {
+        out.flush()?;
+        Ok(valid)
+    }).map_err(error)?;
+    if !valid {
+        return Err(Failure::Error(format!("{}: corrupt input", label)));
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut opts = match parse_args(env::args().skip(1)) {
+        Ok(Some(opts)) => opts,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(msg) => {
+            eprintln!("xsnappy: {}\n\n{}", msg, USAGE);
+            process::exit(1);
+        }
+    };
+    if opts.files.is_empty() {
+        opts.files.push("-".to_string());
+    }
+
+    let mut status = 0;
+    for name in opts.files.iter() {
+        let ret = match opts.command {
+            Command::Inspect => process_inspect(&opts, name),
+            Command::Transform(mode) if name == "-" => process_stdin(&opts, mode),
+            Command::Transform(mode) => process_file(&opts, mode, name),
+        };
+        match ret {
+            Ok(()) => {}
+            Err(Failure::Error(msg)) => {
+                eprintln!("xsnappy: {}", msg);
+                status = 1;
+            }
+            Err(Failure::Warning(msg)) => {
+                eprintln!("xsnappy: {}", msg);
+                if status == 0 {
+                    status = 2;
+                }
+            }
+        }
+    }
+    process::exit(status);
+}
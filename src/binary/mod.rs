@@ -10,13 +10,20 @@ pub fn put_uvarint(buf: &mut [u8], mut x: u64) -> usize {
     (i + 1) as usize
 }
 
+/// Decode the varint at the start of `buf`, like Go's `binary.Uvarint`. Return the value and
+/// the number of bytes read, 0 if `buf` ends inside the varint, or minus the number of bytes
+/// read if the value overflows 64 bits: at a 10th byte above 1, or at an 11th byte.
 pub fn uvarint(buf: &[u8]) -> (u64, isize) {
     let mut x: u64 = 0;
     let mut s: u32 = 0;
     for i in 0..buf.len() {
+        if i == 10 {
+            // Overflow, like Go's binary.Uvarint with 10 continuation bytes.
+            return (0, -((i + 1) as isize))
+        }
         let b = buf[i];
         if b < 0x80 {
-            if i > 9 || i == 9 && b > 1 {
+            if i == 9 && b > 1 {
                 return (0, -1 * (i + 1) as isize)
             }
             return (x | ((b as u64) << s) as u64, (i + 1) as isize)
@@ -36,4 +43,30 @@ pub fn load64(b: &[u8], i: usize) -> u64 {
     let b2 = &b[i..i+8];
     return b2[0] as u64 | (b2[1] as u64) << 8 | (b2[2] as u64) << 16 | (b2[3] as u64) << 24 |
         (b2[4] as u64) << 32 | (b2[5] as u64) << 40 | (b2[6] as u64) << 48 | (b2[7] as u64) << 56
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::uvarint;
+
+    #[test]
+    fn uvarint_overflow() {
+        let mut max = [0xff; 10];
+        max[9] = 0x01;
+        assert_eq!(uvarint(&max), (u64::MAX, 10));
+        let mut top = [0x80; 10];
+        top[9] = 0x01;
+        assert_eq!(uvarint(&top), (1 << 63, 10));
+        // The 10th byte holds the 64th bit only.
+        max[9] = 0x02;
+        assert_eq!(uvarint(&max), (0, -10));
+        // An 11th byte always overflows, even if the value would fit.
+        let mut long = [0x80; 11];
+        long[10] = 0x00;
+        assert_eq!(uvarint(&long), (0, -11));
+        assert_eq!(uvarint(&[0xff; 11]), (0, -11));
+        // Ten continuation bytes are not an overflow until the 11th byte comes.
+        assert_eq!(uvarint(&[0xff; 10]), (0, 0));
+        assert_eq!(uvarint(&[]), (0, 0));
+    }
+}
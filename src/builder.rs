@@ -0,0 +1,148 @@
+//! A builder of raw Snappy blocks with explicit operations, to hand-craft
+//! test vectors for decoders.
+//!
+//! Every operation is written exactly as asked, including non-minimal encodings,
+//! so the block is only as valid as the operations and the length header are.
+//!
+//! # Examples:
+//!
+//! ```rust
+//! use xsnappy::builder::StreamBuilder;
+//! use xsnappy::{decompress_to_vec, validate};
+//!
+//! // "ab" with a 5-byte literal header, then an overlapping copy.
+//! let block = StreamBuilder::new()
+//!     .literal_with_len_bytes(b"ab", 4)
+//!     .copy4(2, 6)
+//!     .build();
+//! assert_eq!(decompress_to_vec(&block).unwrap(), b"abababab");
+//!
+//! // A copy from before the start of the data.
+//! let block = StreamBuilder::new().literal(b"ab").copy1(3, 4).build();
+//! assert!(validate(&block).is_err());
+//! ```
+
+use crate::binary::put_uvarint;
+use crate::error::{TAG_LITERAL, TAG_COPY1, TAG_COPY2, TAG_COPY4};
+use alloc::vec::Vec;
+
+/// A builder of a raw Snappy block. See the [module documentation](index.html).
+#[derive(Debug, Clone, Default)]
+pub struct StreamBuilder {
+    body: Vec<u8>,
+    decoded_len: u64,
+    len_header: Option<u64>,
+}
+
+impl StreamBuilder {
+    /// Create a builder of an empty block.
+    pub fn new() -> StreamBuilder {
+        StreamBuilder::default()
+    }
+
+    /// Append a literal of `data` with the shortest header.
+    pub fn literal(&mut self, data: &[u8]) -> &mut StreamBuilder {
+        let len_bytes = match data.len().saturating_sub(1) {
+            0..=59 => 0,
+            0x3c..=0xff => 1,
+            0x100..=0xffff => 2,
+            0x10000..=0xffffff => 3,
+            _ => 4
+        };
+        self.literal_with_len_bytes(data, len_bytes)
+    }
+
+    /// Append a literal of `data` whose length is stored in `len_bytes` bytes
+    /// after the tag, from 1 to 4, or in the tag itself if `len_bytes` is 0.
+    /// # Panics:
+    /// Panics if `data` is empty or its length doesn't fit in `len_bytes`.
+    pub fn literal_with_len_bytes(&mut self, data: &[u8], len_bytes: usize) -> &mut StreamBuilder {
+        assert!(!data.is_empty(), "snappy: empty literal");
+        self.literal_header(data.len() as u64, len_bytes);
+        self.body.extend_from_slice(data);
+        self
+    }
+
+    /// Append only the header of a literal of `len` bytes, like `literal_with_len_bytes`,
+    /// and count `len` bytes towards the decoded length. The literal bytes may follow
+    /// with `raw`, or be missing on purpose.
+    /// # Panics:
+    /// Panics if `len` is 0 or doesn't fit in `len_bytes`.
+    pub fn literal_header(&mut self, len: u64, len_bytes: usize) -> &mut StreamBuilder {
+        assert!(len >= 1, "snappy: empty literal");
+        let n = len - 1;
+        if len_bytes == 0 {
+            assert!(n < 60, "snappy: literal length {} doesn't fit in the tag", len);
+            self.body.push((n as u8) << 2 | TAG_LITERAL);
+        } else {
+            assert!(len_bytes <= 4 && n >> (8 * len_bytes) == 0,
+                    "snappy: literal length {} doesn't fit in {} bytes", len, len_bytes);
+            self.body.push((59 + len_bytes as u8) << 2 | TAG_LITERAL);
+            self.body.extend_from_slice(&n.to_le_bytes()[..len_bytes]);
+        }
+        self.decoded_len += len;
+        self
+    }
+
+    /// Append a copy of `len` bytes from `offset` bytes back with a 2-byte tag.
+    /// # Panics:
+    /// Panics if `len` is not in 4..=11 or `offset` is not below 2048.
+    pub fn copy1(&mut self, offset: usize, len: usize) -> &mut StreamBuilder {
+        assert!((4..=11).contains(&len), "snappy: copy1 length {} out of 4..=11", len);
+        assert!(offset < 2048, "snappy: copy1 offset {} out of 0..2048", offset);
+        self.body.push(((offset >> 8) as u8) << 5 | ((len - 4) as u8) << 2 | TAG_COPY1);
+        self.body.push(offset as u8);
+        self.decoded_len += len as u64;
+        self
+    }
+
+    /// Append a copy of `len` bytes from `offset` bytes back with a 3-byte tag.
+    /// # Panics:
+    /// Panics if `len` is not in 1..=64 or `offset` is not below 65536.
+    pub fn copy2(&mut self, offset: usize, len: usize) -> &mut StreamBuilder {
+        assert!((1..=64).contains(&len), "snappy: copy2 length {} out of 1..=64", len);
+        assert!(offset < 65536, "snappy: copy2 offset {} out of 0..65536", offset);
+        self.body.push(((len - 1) as u8) << 2 | TAG_COPY2);
+        self.body.extend_from_slice(&(offset as u16).to_le_bytes());
+        self.decoded_len += len as u64;
+        self
+    }
+
+    /// Append a copy of `len` bytes from `offset` bytes back with a 5-byte tag.
+    /// # Panics:
+    /// Panics if `len` is not in 1..=64.
+    pub fn copy4(&mut self, offset: u32, len: usize) -> &mut StreamBuilder {
+        assert!((1..=64).contains(&len), "snappy: copy4 length {} out of 1..=64", len);
+        self.body.push(((len - 1) as u8) << 2 | TAG_COPY4);
+        self.body.extend_from_slice(&offset.to_le_bytes());
+        self.decoded_len += len as u64;
+        self
+    }
+
+    /// Append `bytes` as they are, without counting them towards the decoded length.
+    pub fn raw(&mut self, bytes: &[u8]) -> &mut StreamBuilder {
+        self.body.extend_from_slice(bytes);
+        self
+    }
+
+    /// Write `len` to the length header instead of the sum of the operation lengths.
+    pub fn decoded_len(&mut self, len: u64) -> &mut StreamBuilder {
+        self.len_header = Some(len);
+        self
+    }
+
+    /// Return the length the operations decode to.
+    pub fn ops_len(&self) -> u64 {
+        self.decoded_len
+    }
+
+    /// Return the block: the length header followed by the operations.
+    pub fn build(&self) -> Vec<u8> {
+        let mut header = [0; 10];
+        let n = put_uvarint(&mut header, self.len_header.unwrap_or(self.decoded_len));
+        let mut block = Vec::with_capacity(n + self.body.len());
+        block.extend_from_slice(&header[..n]);
+        block.extend_from_slice(&self.body);
+        block
+    }
+}
@@ -0,0 +1,137 @@
+//! A tokio-util codec for messages compressed one by one, enabled by the `tokio-util` feature.
+
+use crate::binary::{put_uvarint, uvarint};
+use crate::encode::{max_encode_len, Encoder as BlockEncoder};
+use crate::decode::{decode, decode_len};
+use crate::error::{SnappyError, CorruptKind};
+use bytes::{Buf, Bytes, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// The default max decoded length of a frame, 8 MiB.
+const DEFAULT_MAX_FRAME_LEN: usize = 8 * 1024 * 1024;
+
+/// The max length of the varint before each block.
+const MAX_VARINT_LEN: usize = 10;
+
+/// A codec that frames every message as a varint of its encoded length
+/// followed by the message encoded as a Snappy block.
+///
+/// Frames decoding to more than `max_frame_len` bytes are rejected with
+/// `SnappyError::LimitExceeded`, by their length before they are buffered, and
+/// by their block header before they are decoded. Corrupt frames fail with the
+/// `SnappyError` of the block. Both are returned as `io::Error`s of kind `InvalidData`.
+/// The encoder keeps its hash table and buffer across frames.
+///
+/// # Examples:
+///
+/// ```rust
+/// use bytes::{Bytes, BytesMut};
+/// use tokio_util::codec::{Decoder, Encoder};
+/// use xsnappy::SnappyCodec;
+///
+/// let mut codec = SnappyCodec::new();
+/// let mut buf = BytesMut::new();
+/// codec.encode(Bytes::from_static(b"hello world! hello world!"), &mut buf).unwrap();
+/// let msg = codec.decode(&mut buf).unwrap().unwrap();
+/// assert_eq!(&msg[..], b"hello world! hello world!");
+/// ```
+pub struct SnappyCodec {
+    enc: BlockEncoder,
+    buf: Vec<u8>,
+    max_frame_len: usize,
+}
+
+impl SnappyCodec {
+    /// Create a codec with a max decoded frame length of 8 MiB.
+    pub fn new() -> SnappyCodec {
+        SnappyCodec::with_max_frame_len(DEFAULT_MAX_FRAME_LEN)
+    }
+
+    /// Create a codec that rejects frames decoding to more than `max_frame_len` bytes.
+    pub fn with_max_frame_len(max_frame_len: usize) -> SnappyCodec {
+        SnappyCodec { enc: BlockEncoder::new(), buf: Vec::new(), max_frame_len }
+    }
+
+    /// Return the max decoded length of a frame.
+    pub fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    /// Set the max decoded length of a frame.
+    pub fn set_max_frame_len(&mut self, max_frame_len: usize) {
+        self.max_frame_len = max_frame_len;
+    }
+
+    /// Return the max encoded length of a frame, which no encoder exceeds
+    /// for a message of `max_frame_len` bytes.
+    fn max_block_len(&self) -> usize {
+        match max_encode_len(self.max_frame_len) {
+            0 => usize::MAX,
+            n => n
+        }
+    }
+}
+
+impl Default for SnappyCodec {
+    fn default() -> SnappyCodec {
+        SnappyCodec::new()
+    }
+}
+
+impl Encoder<Bytes> for SnappyCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> io::Result<()> {
+        if item.len() > self.max_frame_len {
+            return Err(SnappyError::LimitExceeded.into())
+        }
+        let n = max_encode_len(item.len());
+        if n == 0 {
+            return Err(SnappyError::EncodeTooLarge.into())
+        }
+        if self.buf.len() < n {
+            self.buf.resize(n, 0);
+        }
+        let len = self.enc.compress(&mut self.buf, &item)?;
+        let mut header = [0; MAX_VARINT_LEN];
+        let h = put_uvarint(&mut header, len as u64);
+        dst.reserve(h + len);
+        dst.extend_from_slice(&header[..h]);
+        dst.extend_from_slice(&self.buf[..len]);
+        Ok(())
+    }
+}
+
+impl Decoder for SnappyCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        let (len, h) = uvarint(src);
+        if h == 0 {
+            return Ok(None)
+        }
+        if h < 0 {
+            return Err(SnappyError::corrupt(CorruptKind::Header, 0, 0, None).into())
+        }
+        let h = h as usize;
+        if len > self.max_block_len() as u64 {
+            return Err(SnappyError::LimitExceeded.into())
+        }
+        let len = len as usize;
+        if src.len() < h + len {
+            src.reserve(h + len - src.len());
+            return Ok(None)
+        }
+        src.advance(h);
+        let block = src.split_to(len);
+        let d_len = decode_len(&block).map_err(|err| err.shift(h, 0))?;
+        if d_len > self.max_frame_len {
+            return Err(SnappyError::LimitExceeded.into())
+        }
+        let mut msg = BytesMut::zeroed(d_len);
+        decode(&mut msg, &block).map_err(|err| err.shift(h, 0))?;
+        Ok(Some(msg))
+    }
+}
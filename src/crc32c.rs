@@ -0,0 +1,171 @@
+/*!
+CRC-32C (Castagnoli) checksums, as used by the Snappy framing format.
+
+The checksum is computed with the SSE4.2 `crc32` instruction when the CPU
+supports it, and with a portable slicing-by-8 implementation otherwise.
+Without the `std` feature, the CPU can't be queried, so the instruction is
+only used when the target enables it, e.g. with `-C target-feature=+sse4.2`.
+
+# Examples:
+
+```rust
+use xsnappy::crc32c::{crc32c, crc32c_update, mask, unmask};
+
+let crc = crc32c(b"123456789");
+assert_eq!(crc, 0xe3069283);
+assert_eq!(crc32c_update(crc32c(b"1234"), b"56789"), crc);
+assert_eq!(unmask(mask(crc)), crc);
+```
+*/
+
+/// The CRC-32C (Castagnoli) polynomial in reversed bit order.
+const CASTAGNOLI: u32 = 0x82f63b78;
+
+/// The constant added by `mask`.
+const MASK_DELTA: u32 = 0xa282ead8;
+
+/// `TABLES[0]` is the classic byte-at-a-time table, `TABLES[k][b]` is the
+/// checksum of byte `b` followed by `k` zero bytes.
+static TABLES: [[u32; 256]; 8] = make_tables();
+
+const fn make_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0u32; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            if crc & 1 == 1 {
+                crc = (crc >> 1) ^ CASTAGNOLI;
+            } else {
+                crc >>= 1;
+            }
+            j += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 256 {
+        let mut k = 1;
+        while k < 8 {
+            let prev = tables[k - 1][i];
+            tables[k][i] = tables[0][(prev & 0xff) as usize] ^ (prev >> 8);
+            k += 1;
+        }
+        i += 1;
+    }
+    tables
+}
+
+/// Return the CRC-32C checksum of `data`.
+pub fn crc32c(data: &[u8]) -> u32 {
+    crc32c_update(0, data)
+}
+
+/// Return the checksum of the data checksummed into `crc` followed by `data`.
+/// `crc32c_update(0, data)` is the same as `crc32c(data)`.
+pub fn crc32c_update(crc: u32, data: &[u8]) -> u32 {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        #[cfg(feature = "std")]
+        let sse42 = is_x86_feature_detected!("sse4.2");
+        // Without std, the instruction is used only if the target enables it.
+        #[cfg(not(feature = "std"))]
+        let sse42 = cfg!(target_feature = "sse4.2");
+        if sse42 {
+            return unsafe { !update_sse42(!crc, data) }
+        }
+    }
+    !update_slicing_by_8(!crc, data)
+}
+
+/// Return the masked checksum stored by the Snappy framing format.
+/// Checksumming data that contains its own checksum is problematic,
+/// so the framing format stores this rotated and offset value instead.
+pub fn mask(crc: u32) -> u32 {
+    crc.rotate_right(15).wrapping_add(MASK_DELTA)
+}
+
+/// Return the checksum that was masked by `mask`.
+pub fn unmask(masked: u32) -> u32 {
+    masked.wrapping_sub(MASK_DELTA).rotate_left(15)
+}
+
+fn update_slicing_by_8(mut crc: u32, mut data: &[u8]) -> u32 {
+    while data.len() >= 8 {
+        let lo = crc ^ u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        crc = TABLES[7][(lo & 0xff) as usize] ^
+            TABLES[6][((lo >> 8) & 0xff) as usize] ^
+            TABLES[5][((lo >> 16) & 0xff) as usize] ^
+            TABLES[4][(lo >> 24) as usize] ^
+            TABLES[3][data[4] as usize] ^
+            TABLES[2][data[5] as usize] ^
+            TABLES[1][data[6] as usize] ^
+            TABLES[0][data[7] as usize];
+        data = &data[8..];
+    }
+    for &b in data {
+        crc = TABLES[0][((crc as u8) ^ b) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.2")]
+unsafe fn update_sse42(crc: u32, data: &[u8]) -> u32 {
+    use core::arch::x86_64::{_mm_crc32_u64, _mm_crc32_u8};
+
+    let mut crc = crc as u64;
+    let mut chunks = data.chunks_exact(8);
+    for c in &mut chunks {
+        let v = u64::from_le_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]);
+        crc = _mm_crc32_u64(crc, v);
+    }
+    let mut crc = crc as u32;
+    for &b in chunks.remainder() {
+        crc = _mm_crc32_u8(crc, b);
+    }
+    crc
+}
+
+#[cfg(target_arch = "x86")]
+#[target_feature(enable = "sse4.2")]
+unsafe fn update_sse42(mut crc: u32, data: &[u8]) -> u32 {
+    use core::arch::x86::{_mm_crc32_u32, _mm_crc32_u8};
+
+    let mut chunks = data.chunks_exact(4);
+    for c in &mut chunks {
+        crc = _mm_crc32_u32(crc, u32::from_le_bytes([c[0], c[1], c[2], c[3]]));
+    }
+    for &b in chunks.remainder() {
+        crc = _mm_crc32_u8(crc, b);
+    }
+    crc
+}
+
+#[cfg(all(test, feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+mod tests {
+    use super::{update_slicing_by_8, update_sse42};
+
+    #[test]
+    fn sse42_matches_slicing_by_8() {
+        if !is_x86_feature_detected!("sse4.2") {
+            return
+        }
+        let mut x = 1u32;
+        let data: Vec<u8> = (0..5000).map(|_| { x ^= x << 13; x ^= x >> 17; x ^= x << 5; x as u8 }).collect();
+        // Every length and alignment around the 8-byte steps, and longer inputs.
+        for start in 0..8 {
+            for len in (0..40).chain([100, 1000, 4000].iter().cloned()) {
+                let p = &data[start..start + len];
+                for &crc in [0, !0, 0x12345678].iter() {
+                    let want = update_slicing_by_8(crc, p);
+                    assert_eq!(unsafe { update_sse42(crc, p) }, want, "start {} len {} crc {:#x}", start, len, crc);
+                }
+            }
+        }
+        assert_eq!(!update_slicing_by_8(!0, b"123456789"), 0xe3069283);
+        assert_eq!(!unsafe { update_sse42(!0, b"123456789") }, 0xe3069283);
+    }
+}
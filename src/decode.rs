@@ -1,115 +1,299 @@
 use crate::binary::uvarint;
-use crate::error::{SnappyError, TAG_LITERAL, TAG_COPY1, TAG_COPY2, TAG_COPY4};
-use std::ptr::{copy_nonoverlapping, copy};
+use crate::ops::Ops;
+use crate::error::{SnappyError, CorruptKind, TagKind, TAG_LITERAL, TAG_COPY1, TAG_COPY2, TAG_COPY4};
+use core::ptr::{copy_nonoverlapping, copy};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 
 /// Return the exact length of decoded data.
 pub fn decode_len(src: &[u8]) -> Result<usize, SnappyError> {
-    let (v, _, err) = _decode_len(src);
-    return match err {
-        SnappyError::None => Ok(v),
-        _ => Err(err)
-    }
+    let (v, _) = _decode_len(src)?;
+    Ok(v)
+}
+
+/// Return the longest data a Snappy block of `src_len` bytes can decode to.
+/// The densest tag is a copy2 of 64 bytes, which takes 3 bytes.
+#[cfg(feature = "std")]
+pub(crate) fn max_decode_len(src_len: usize) -> usize {
+    (src_len / 3 + 1).saturating_mul(64)
 }
 
 /// Return `block_len` and `header_len`.
-fn _decode_len(src: &[u8]) -> (usize, usize, SnappyError) {
+pub(crate) fn _decode_len(src: &[u8]) -> Result<(usize, usize), SnappyError> {
     let (v, n) = uvarint(src);
     if n <= 0 || v > 0xffffffff {
-        return (0, 0, SnappyError::Corrupt)
+        return Err(SnappyError::corrupt(CorruptKind::Header, 0, 0, None))
     }
     let word_size = 32 << (usize::MAX >> 32 & 1);
     if word_size == 32 && v > 0x7fffffff {
-        return (0, 0, SnappyError::DecodeTooLarge)
+        return Err(SnappyError::DecodeTooLarge)
     }
-    return (v as usize, n as usize, SnappyError::None)
+    Ok((v as usize, n as usize))
 }
 
-pub fn decode(dst: &mut [u8], src: &[u8]) -> Result<usize, SnappyError> {
-    let (d_len, s, err) = _decode_len(src);
-    match err {
-        SnappyError::None => {}
-        _ => return Err(err)
+/// Limits on the decoded data a decoder accepts, to defend against decompression
+/// bombs: small inputs whose header claims a huge decoded length.
+/// The limits are checked against the header before anything is allocated or decoded.
+/// `None` means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// The max decoded length. Streaming readers apply it to the whole stream.
+    pub max_decoded_len: Option<usize>,
+    /// The max ratio of the decoded length to the encoded length.
+    /// Streaming readers apply it to every compressed block.
+    pub max_ratio: Option<usize>,
+}
+
+impl DecodeOptions {
+    /// Check `d_len` bytes of decoded data against `max_decoded_len`.
+    pub(crate) fn check_len(&self, d_len: usize) -> Result<(), SnappyError> {
+        match self.max_decoded_len {
+            Some(max) if d_len > max => Err(SnappyError::LimitExceeded),
+            _ => Ok(())
+        }
+    }
+
+    /// Check `d_len` bytes decoded from `src_len` bytes against `max_ratio`.
+    pub(crate) fn check_ratio(&self, d_len: usize, src_len: usize) -> Result<(), SnappyError> {
+        match self.max_ratio {
+            Some(max) if d_len > src_len.saturating_mul(max) => Err(SnappyError::LimitExceeded),
+            _ => Ok(())
+        }
+    }
+
+    /// Check the block `src`, which decodes to `d_len` bytes, against both limits.
+    pub(crate) fn check(&self, d_len: usize, src: &[u8]) -> Result<(), SnappyError> {
+        self.check_len(d_len)?;
+        self.check_ratio(d_len, src.len())
     }
-    let mut p;
+}
+
+/// Decode `src` to `dst` if it is within the limits of `options`.
+pub fn decode_with_options(dst: &mut [u8], src: &[u8], options: &DecodeOptions) -> Result<usize, SnappyError> {
+    options.check(decode_len(src)?, src)?;
+    decode(dst, src)
+}
+
+pub fn decode(dst: &mut [u8], src: &[u8]) -> Result<usize, SnappyError> {
+    let (d_len, s) = _decode_len(src)?;
+    let p;
     if d_len <= dst.len() {
         p = &mut dst[..d_len];
     } else {
         return Err(SnappyError::DstTooSmall)
     }
 
-    let err = _decode(p, &src[s..]);
-    return match err {
-        SnappyError::None => Ok(d_len),
-        _ => Err(err)
+    _decode::<false>(p, src, s)?;
+    Ok(d_len)
+}
+
+/// A reusable decoder. It decodes into an owned buffer that grows to the
+/// largest decoded length seen, and is reused without being zeroed again.
+///
+/// # Examples:
+///
+/// ```rust
+/// use xsnappy::{Decoder, compress_to_vec};
+///
+/// let mut dec = Decoder::new();
+/// for msg in [&b"hello hello hello"[..], &b"world"[..]].iter() {
+///     let compressed = compress_to_vec(msg).unwrap();
+///     assert_eq!(dec.decompress(&compressed).unwrap(), *msg);
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+pub struct Decoder {
+    buf: Vec<u8>,
+    options: DecodeOptions,
+}
+
+#[cfg(feature = "alloc")]
+impl Decoder {
+    /// Create a decoder.
+    pub fn new() -> Decoder {
+        Decoder::with_options(DecodeOptions::default())
+    }
+
+    /// Create a decoder that rejects data beyond the limits of `options`.
+    pub fn with_options(options: DecodeOptions) -> Decoder {
+        Decoder { buf: Vec::new(), options }
+    }
+
+    /// Decode `src` and return the decoded data, which is valid until the next call.
+    pub fn decompress(&mut self, src: &[u8]) -> Result<&[u8], SnappyError> {
+        let (d_len, s) = _decode_len(src)?;
+        self.options.check(d_len, src)?;
+        if self.buf.len() < d_len {
+            self.buf.resize(d_len, 0);
+        }
+        let dst = &mut self.buf[..d_len];
+        _decode::<false>(dst, src, s)?;
+        Ok(dst)
     }
 }
 
-fn _decode(dst: &mut [u8], src: &[u8]) -> SnappyError {
-    let (mut d, mut s, mut offset, mut length): (usize, usize, usize, usize) = (0, 0, 0, 0) ;
-    while s < src.len() {
-        match src[s] & 0x03 {
-            TAG_LITERAL => {
-                let mut x = (src[s] >> 2) as u32;
-                if x < 60 {
-                    s += 1;
-                } else if x == 60 {
-                    s += 2;
-                    if s > src.len() { return SnappyError::Corrupt }
-                    x = src[s-1] as u32;
-                } else if x == 61 {
-                    s += 3;
-                    if s > src.len() { return SnappyError::Corrupt }
-                    x = src[s-2] as u32 | (src[s-1] as u32) << 8;
-                } else if x == 62 {
-                    s += 4;
-                    if s > src.len() { return SnappyError::Corrupt }
-                    x = src[s-3] as u32 | (src[s-2] as u32) << 8 | (src[s-1] as u32) << 16;
-                } else if x == 63 {
-                    s += 5;
-                    if s > src.len() { return SnappyError::Corrupt }
-                    x = src[s-4] as u32 | (src[s-3] as u32) << 8
-                        | (src[s-2] as u32) << 16 | (src[s-1] as u32) << 24;
-                }
+#[cfg(feature = "alloc")]
+impl Default for Decoder {
+    fn default() -> Decoder {
+        Decoder::new()
+    }
+}
 
-                length = x as usize + 1;
-                if length <= 0 { return SnappyError::UnsupportedLiteralLength }
-                if length > dst.len() - d || length > src.len() - s {
-                    return SnappyError::Corrupt
+/// A tag of the encoded data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Tag {
+    /// A literal of `len` bytes.
+    Literal { len: usize },
+    /// A copy of `len` bytes from `offset` bytes back, encoded with a tag of `kind`.
+    Copy { kind: TagKind, offset: usize, len: usize },
+}
+
+/// Read the tag at `src[s..]`, after `d` bytes have been decoded. Return it with
+/// the position after it, which for a literal is its first byte.
+/// All bytes of a literal are checked to be in `src`.
+#[inline(always)]
+pub(crate) fn read_tag(src: &[u8], s: usize, d: usize) -> Result<(Tag, usize), SnappyError> {
+    let truncated = |tag| SnappyError::corrupt(CorruptKind::TruncatedTag, s, d, Some(tag));
+    let mut i = s;
+    match src[i] & 0x03 {
+        TAG_LITERAL => {
+            let mut x = (src[i] >> 2) as u32;
+            if x < 60 {
+                i += 1;
+            } else if x == 60 {
+                i += 2;
+                if i > src.len() { return Err(truncated(TagKind::Literal)) }
+                x = src[i-1] as u32;
+            } else if x == 61 {
+                i += 3;
+                if i > src.len() { return Err(truncated(TagKind::Literal)) }
+                x = src[i-2] as u32 | (src[i-1] as u32) << 8;
+            } else if x == 62 {
+                i += 4;
+                if i > src.len() { return Err(truncated(TagKind::Literal)) }
+                x = src[i-3] as u32 | (src[i-2] as u32) << 8 | (src[i-1] as u32) << 16;
+            } else {
+                i += 5;
+                if i > src.len() { return Err(truncated(TagKind::Literal)) }
+                x = src[i-4] as u32 | (src[i-3] as u32) << 8
+                    | (src[i-2] as u32) << 16 | (src[i-1] as u32) << 24;
+            }
+
+            let len = match (x as usize).checked_add(1) {
+                Some(len) => len,
+                None => return Err(SnappyError::UnsupportedLiteralLength)
+            };
+            if len > src.len() - i {
+                return Err(SnappyError::corrupt(CorruptKind::LiteralPastInput(len), s, d, Some(TagKind::Literal)))
+            }
+            Ok((Tag::Literal { len }, i))
+        }
+        TAG_COPY1 => {
+            i += 2;
+            if i > src.len() { return Err(truncated(TagKind::Copy1)) }
+            let len = 4 + ((src[i-2] as usize) >> 2 & 0x7);
+            let offset = (((src[i-2] as u32) & 0xe0) << 3 | src[i-1] as u32) as usize;
+            Ok((Tag::Copy { kind: TagKind::Copy1, offset, len }, i))
+        }
+        TAG_COPY2 => {
+            i += 3;
+            if i > src.len() { return Err(truncated(TagKind::Copy2)) }
+            let len = 1 + ((src[i-3] as usize) >> 2);
+            let offset = (src[i-2] as u32 | (src[i-1] as u32) << 8) as usize;
+            Ok((Tag::Copy { kind: TagKind::Copy2, offset, len }, i))
+        }
+        _ => {
+            debug_assert_eq!(src[i] & 0x03, TAG_COPY4);
+            i += 5;
+            if i > src.len() { return Err(truncated(TagKind::Copy4)) }
+            let len = 1 + ((src[i-5] as usize) >> 2);
+            let offset = (src[i-4] as u32 | (src[i-3] as u32) << 8
+                | (src[i-2] as u32) << 16 | (src[i-1] as u32) << 24) as usize;
+            Ok((Tag::Copy { kind: TagKind::Copy4, offset, len }, i))
+        }
+    }
+}
+
+/// Check a copy of the tag at `s` against the `d` bytes decoded so far
+/// and the `remaining` bytes left to decode.
+#[inline(always)]
+pub(crate) fn check_copy(kind: TagKind, offset: usize, len: usize, s: usize, d: usize, remaining: usize) -> Result<(), SnappyError> {
+    if offset == 0 {
+        return Err(SnappyError::corrupt(CorruptKind::ZeroOffset, s, d, Some(kind)))
+    }
+    if d < offset {
+        return Err(SnappyError::corrupt(CorruptKind::OffsetTooLarge(offset), s, d, Some(kind)))
+    }
+    if len > remaining {
+        return Err(SnappyError::corrupt(CorruptKind::PastDecodedLen(len), s, d, Some(kind)))
+    }
+    Ok(())
+}
+
+/// Check that `src` is valid encoded data without decoding it.
+/// Return the exact length of decoded data.
+pub fn validate(src: &[u8]) -> Result<usize, SnappyError> {
+    let mut ops = Ops::new(src)?;
+    let d_len = ops.decoded_len();
+    ops.try_for_each(|op| op.map(|_| ()))?;
+    Ok(d_len)
+}
+
+/// Read the length of an S2 repeat, the copy1 at `s` with offset 0 and length `len`,
+/// whose tag ends at `next`. Lengths 9 to 11 take 1 to 3 more bytes for longer repeats.
+/// Return the length and the position after the repeat.
+#[inline(always)]
+fn read_repeat(src: &[u8], s: usize, next: usize, len: usize, d: usize) -> Result<(usize, usize), SnappyError> {
+    let (n, base) = match len {
+        9 => (1, 8),
+        10 => (2, (1 << 8) + 4),
+        11 => (3, (1 << 16) + 4),
+        _ => return Ok((len, next))
+    };
+    if next + n > src.len() {
+        return Err(SnappyError::corrupt(CorruptKind::TruncatedTag, s, d, Some(TagKind::Copy1)))
+    }
+    let mut x = 0;
+    for (k, &b) in src[next..next + n].iter().enumerate() {
+        x |= (b as usize) << (8 * k);
+    }
+    Ok((base + x, next + n))
+}
+
+/// Decode the tags of `src` from position `s` on to `dst`, which has the exact decoded length.
+/// With `S2`, a copy1 with offset 0 repeats the offset of the previous copy, as in S2.
+pub(crate) fn _decode<const S2: bool>(dst: &mut [u8], src: &[u8], mut s: usize) -> Result<(), SnappyError> {
+    let mut d = 0;
+    let mut last_offset = 0;
+    while s < src.len() {
+        let (offset, length) = match read_tag(src, s, d)? {
+            (Tag::Literal { len }, next) => {
+                if len > dst.len() - d {
+                    return Err(SnappyError::corrupt(CorruptKind::PastDecodedLen(len), s, d, Some(TagKind::Literal)))
                 }
+                s = next;
                 unsafe {
                     clone(dst.as_mut_ptr().offset(d as isize),
                           src.as_ptr().offset(s as isize),
-                          dst.len()-d, length);
+                          dst.len()-d, len);
                 }
-                d += length;
-                s += length;
+                d += len;
+                s += len;
                 continue
             }
-            TAG_COPY1 => {
-                s += 2;
-                if s > src.len() { return SnappyError::Corrupt }
-                length = 4 + ((src[s-2] as usize) >> 2 & 0x7);
-                offset = (((src[s-2] as u32) & 0xe0) << 3 | src[s-1] as u32) as usize;
-            }
-            TAG_COPY2 => {
-                s += 3;
-                if s > src.len() { return SnappyError::Corrupt }
-                length = 1 + ((src[s-3] as usize) >> 2);
-                offset = (src[s-2] as u32 | (src[s-1] as u32) << 8) as usize;
-            }
-            TAG_COPY4 => {
-                s += 5;
-                if s > src.len() { return SnappyError::Corrupt }
-                length = 1 + ((src[s-5] as usize) >> 2);
-                offset = (src[s-4] as u32 | (src[s-3] as u32) << 8
-                    | (src[s-2] as u32) << 16 | (src[s-1] as u32) << 24) as usize;
+            (Tag::Copy { kind, offset, len }, next) => {
+                let (offset, len, next) = if S2 && kind == TagKind::Copy1 && offset == 0 {
+                    let (len, next) = read_repeat(src, s, next, len, d)?;
+                    (last_offset, len, next)
+                } else {
+                    (offset, len, next)
+                };
+                check_copy(kind, offset, len, s, d, dst.len() - d)?;
+                last_offset = offset;
+                s = next;
+                (offset, len)
             }
-            _ => {}
-        }
-
-        if offset <= 0 || d < offset || length > dst.len() - d {
-            return SnappyError::Corrupt
-        }
+        };
 
         let end = d + length;
         if offset >= 8 && length <= 16 && d + 16 <= dst.len() {
@@ -158,9 +342,9 @@ fn _decode(dst: &mut [u8], src: &[u8]) -> SnappyError {
         }
     }
     if d != dst.len() {
-        return SnappyError::Corrupt
+        return Err(SnappyError::corrupt(CorruptKind::ShortOutput, s, d, None))
     }
-    SnappyError::None
+    Ok(())
 }
 
 unsafe fn clone<T: Clone>(mut dst: *mut T, src: *const T, dst_len: usize, src_len: usize) {
@@ -0,0 +1,110 @@
+use crate::binary::{load32, load64};
+use crate::encode::{emit_literal, emit_copy, emit_remainder, extend_match, INPUT_MARGIN};
+use crate::error::SnappyError;
+
+/// The max log2 of the bucket count of the long hash table.
+const MAX_LONG_TABLE_BITS: u32 = 16;
+/// The max log2 of the entry count of the short hash table.
+const MAX_SHORT_TABLE_BITS: u32 = 14;
+/// The candidates kept per bucket of the long hash table, newest first.
+const LONG_BUCKET_SIZE: usize = 2;
+
+/// The table length `encode_block_better` needs for any block.
+#[cfg(feature = "alloc")]
+pub(crate) const BETTER_TABLE_SIZE: usize = (LONG_BUCKET_SIZE << MAX_LONG_TABLE_BITS) + (1 << MAX_SHORT_TABLE_BITS);
+
+const PRIME_4_BYTES: u32 = 2654435761;
+const PRIME_7_BYTES: u64 = 58295818150454627;
+
+/// Hash the low 4 bytes of `u` to `bits` bits.
+fn hash4(u: u64, bits: u32) -> usize {
+    ((u as u32).wrapping_mul(PRIME_4_BYTES) >> (32 - bits)) as usize
+}
+
+/// Hash the low 7 bytes of `u` to `bits` bits.
+fn hash7(u: u64, bits: u32) -> usize {
+    ((u << 8).wrapping_mul(PRIME_7_BYTES) >> (64 - bits)) as usize
+}
+
+/// Insert `s` as the newest candidate of bucket `h`.
+fn insert_long(table: &mut [u16], h: usize, s: usize) {
+    table[h + 1] = table[h];
+    table[h] = s as u16;
+}
+
+/// Encode a block of at most `MAX_BLOCK_SIZE` bytes, looking harder for long matches
+/// than `encode_block`. Every position is looked up in a long hash table of 7-byte
+/// hashes, with two candidates per bucket, and in a short hash table of 4-byte hashes.
+/// The longest of the three candidates wins. `table` must be `BETTER_TABLE_SIZE` long.
+pub(crate) fn encode_block_better(dst: &mut [u8], src: &[u8], table: &mut [u16]) -> Result<usize, SnappyError> {
+    let mut long_bits = 8;
+    while long_bits < MAX_LONG_TABLE_BITS && 1 << long_bits < src.len() {
+        long_bits += 1;
+    }
+    let short_bits = core::cmp::min(long_bits, MAX_SHORT_TABLE_BITS);
+    let (long_table, short_table) = table.split_at_mut(LONG_BUCKET_SIZE << long_bits);
+    let short_table = &mut short_table[..1 << short_bits];
+    long_table.fill(0);
+    short_table.fill(0);
+
+    let s_limit = src.len() - INPUT_MARGIN;
+    let mut next_emit = 0;
+    let mut d = 0;
+    let mut s = 1;
+
+    loop {
+        let mut candidate = 0;
+        let mut length = 0;
+        loop {
+            // Probe less often the longer we go without a match.
+            let next_s = s + ((s - next_emit) >> 6) + 1;
+            if next_s > s_limit {
+                return emit_remainder(dst, src, next_emit, d)
+            }
+            let cv = load64(src, s);
+            let hl = hash7(cv, long_bits) * LONG_BUCKET_SIZE;
+            let hs = hash4(cv, short_bits);
+            let candidates = [long_table[hl] as usize, long_table[hl + 1] as usize, short_table[hs] as usize];
+            insert_long(long_table, hl, s);
+            short_table[hs] = s as u16;
+
+            for &c in candidates.iter() {
+                if load32(src, c) == cv as u32 {
+                    let n = extend_match(src, s + 4, c + 4) - s;
+                    if n > length {
+                        length = n;
+                        candidate = c;
+                    }
+                }
+            }
+            if length > 0 {
+                break
+            }
+            s = next_s;
+        }
+
+        // Extend the match backwards over bytes not emitted yet.
+        while candidate > 0 && s > next_emit && src[candidate - 1] == src[s - 1] {
+            candidate -= 1;
+            s -= 1;
+            length += 1;
+        }
+        if s > next_emit {
+            d += emit_literal(&mut dst[d..], &src[next_emit..s])?;
+        }
+        d += emit_copy(&mut dst[d..], s - candidate, length)?;
+
+        let base = s;
+        s += length;
+        next_emit = s;
+        if s >= s_limit {
+            return emit_remainder(dst, src, next_emit, d)
+        }
+
+        // Index positions inside the match, so that later matches can find them.
+        insert_long(long_table, hash7(load64(src, base + 1), long_bits) * LONG_BUCKET_SIZE, base + 1);
+        insert_long(long_table, hash7(load64(src, s - 2), long_bits) * LONG_BUCKET_SIZE, s - 2);
+        short_table[hash4(load64(src, base + 2), short_bits)] = (base + 2) as u16;
+        short_table[hash4(load64(src, s - 1), short_bits)] = (s - 1) as u16;
+    }
+}
@@ -1,44 +1,159 @@
-use std::fmt::{Display, Formatter};
-use std::fmt;
+use core::fmt::{self, Display, Formatter};
+#[cfg(feature = "std")]
+use std::io;
 
 pub(crate) const TAG_LITERAL: u8 = 0x00;
 pub(crate) const TAG_COPY1: u8 = 0x01;
 pub(crate) const TAG_COPY2: u8 = 0x02;
 pub(crate) const TAG_COPY4: u8 = 0x03;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SnappyError {
-    None,
     EncodeTooLarge,   // encode block is too large
     DecodeTooLarge,   // decode block is too large
     DstTooSmall,
-    Corrupt,
+    Corrupt(Corruption),
     Unsupported,
-    UnsupportedLiteralLength
+    UnsupportedLiteralLength,
+    UnskippableChunk, // reserved unskippable chunk in a framed stream
+    LimitExceeded,    // decoded data exceeds the limits of `DecodeOptions`
+}
+
+/// Where and why decoding failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Corruption {
+    pub kind: CorruptKind,
+    /// The offset in the input of the tag, header or chunk that failed.
+    pub input_offset: usize,
+    /// The number of bytes decoded before the failure.
+    pub output_offset: usize,
+    /// The tag that failed, if the failure is in a tag.
+    pub tag: Option<TagKind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptKind {
+    /// The varint length header is malformed.
+    Header,
+    /// The tag is cut off by the end of the input.
+    TruncatedTag,
+    /// The literal of this many bytes runs past the end of the input.
+    LiteralPastInput(usize),
+    /// The literal or copy of this many bytes runs past the decoded length.
+    PastDecodedLen(usize),
+    /// The copy has offset 0.
+    ZeroOffset,
+    /// The copy has this offset, which reaches before the start of the output.
+    OffsetTooLarge(usize),
+    /// The input ends before the decoded length is reached.
+    ShortOutput,
+    /// A framed stream lacks the stream identifier, or has a wrong one.
+    StreamIdentifier,
+    /// A chunk of a framed stream has an invalid length.
+    ChunkLength,
+    /// The data of a chunk doesn't match its checksum.
+    Checksum,
+    /// A framed stream ends inside a chunk.
+    TruncatedChunk,
+}
+
+/// The kind of a tag of encoded data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagKind {
+    Literal,
+    Copy1,
+    Copy2,
+    Copy4,
+}
+
+impl SnappyError {
+    pub(crate) fn corrupt(kind: CorruptKind, input_offset: usize, output_offset: usize,
+                          tag: Option<TagKind>) -> SnappyError {
+        SnappyError::Corrupt(Corruption { kind, input_offset, output_offset, tag })
+    }
+
+    /// Move the offsets of a corruption by `input` and `output` bytes, for data
+    /// decoded as a part of a larger stream.
+    #[cfg(feature = "std")]
+    pub(crate) fn shift(self, input: usize, output: usize) -> SnappyError {
+        match self {
+            SnappyError::Corrupt(c) => SnappyError::Corrupt(Corruption {
+                input_offset: c.input_offset + input,
+                output_offset: c.output_offset + output,
+                ..c
+            }),
+            err => err
+        }
+    }
 }
 
 impl Display for SnappyError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", str_of_error(self))
+        match self {
+            SnappyError::Corrupt(c) => write!(f, "{}: {}", str_of_error(self), c),
+            _ => write!(f, "{}", str_of_error(self))
+        }
+    }
+}
+
+impl Display for Corruption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let tag = match self.tag {
+            Some(TagKind::Literal) => "literal",
+            Some(TagKind::Copy1) => "copy1",
+            Some(TagKind::Copy2) => "copy2",
+            Some(TagKind::Copy4) => "copy4",
+            None => "tag"
+        };
+        match self.kind {
+            CorruptKind::Header => write!(f, "invalid length header")?,
+            CorruptKind::TruncatedTag => write!(f, "{} cut off by the end of input", tag)?,
+            CorruptKind::LiteralPastInput(len) =>
+                write!(f, "literal of {} bytes exceeds the end of input", len)?,
+            CorruptKind::PastDecodedLen(len) =>
+                write!(f, "{} of {} bytes exceeds the decoded length after {} bytes", tag, len, self.output_offset)?,
+            CorruptKind::ZeroOffset => write!(f, "copy offset 0")?,
+            CorruptKind::OffsetTooLarge(offset) =>
+                write!(f, "copy offset {} exceeds produced {} bytes", offset, self.output_offset)?,
+            CorruptKind::ShortOutput =>
+                write!(f, "input ends after producing {} bytes", self.output_offset)?,
+            CorruptKind::StreamIdentifier => write!(f, "missing or invalid stream identifier")?,
+            CorruptKind::ChunkLength => write!(f, "invalid chunk length")?,
+            CorruptKind::Checksum => write!(f, "checksum mismatch")?,
+            CorruptKind::TruncatedChunk => write!(f, "chunk cut off by the end of input")?,
+        }
+        write!(f, " at input byte {}", self.input_offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SnappyError {}
+
+#[cfg(feature = "std")]
+impl From<SnappyError> for io::Error {
+    fn from(err: SnappyError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
     }
 }
 
-const NONE_ERR_MSG: &str = "snappy: none error";
 const ENCODE_TOO_LARGE_ERR_MSG: &str = "snappy:  encode block is too large";
 const DECODE_TOO_LARGE_ERR_MSG: &str = "snappy:  decode block is too large";
 const DST_TOO_SMALL_ERR_MSG: &str = "snappy: dst len is too small";
 const CORRUPT_ERR_MSG: &str = "snappy: corrupt input";
 const UNSUPPORTED_ERR_MSG: &str = "snappy: unsupported input";
 const UNSUPPORTED_LITERAL_LENGTH_ERR_MSG: &str = "snappy: unsupported literal length";
+const UNSKIPPABLE_CHUNK_ERR_MSG: &str = "snappy: unsupported reserved unskippable chunk";
+const LIMIT_EXCEEDED_ERR_MSG: &str = "snappy: decoded data exceeds the limit";
 
 fn str_of_error(err: &SnappyError) -> &'static str {
-    return match err {
-        SnappyError::None => NONE_ERR_MSG,
+    match err {
         SnappyError::EncodeTooLarge => ENCODE_TOO_LARGE_ERR_MSG,
         SnappyError::DecodeTooLarge => DECODE_TOO_LARGE_ERR_MSG,
         SnappyError::DstTooSmall => DST_TOO_SMALL_ERR_MSG,
-        SnappyError::Corrupt => CORRUPT_ERR_MSG,
+        SnappyError::Corrupt(_) => CORRUPT_ERR_MSG,
         SnappyError::Unsupported => UNSUPPORTED_ERR_MSG,
-        SnappyError::UnsupportedLiteralLength => UNSUPPORTED_LITERAL_LENGTH_ERR_MSG
+        SnappyError::UnsupportedLiteralLength => UNSUPPORTED_LITERAL_LENGTH_ERR_MSG,
+        SnappyError::UnskippableChunk => UNSKIPPABLE_CHUNK_ERR_MSG,
+        SnappyError::LimitExceeded => LIMIT_EXCEEDED_ERR_MSG
     }
 }
@@ -0,0 +1,244 @@
+//! Async versions of `FrameEncoder` and `FrameDecoder` for tokio (the `tokio`
+//! feature) and futures-io (the `futures-io` feature). They share the chunk
+//! logic of the blocking ones, so they produce the same bytes for the same data
+//! and flushes.
+
+use crate::frame::chunk::{ChunkReader, ChunkWriter};
+use crate::decode::DecodeOptions;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// An async writer that compresses data to the Snappy framing format.
+///
+/// It implements tokio's `AsyncWrite` if `W` does and the `tokio` feature is
+/// enabled, and futures-io's `AsyncWrite` if `W` does and the `futures-io`
+/// feature is enabled. Chunks are emitted like `FrameEncoder` does, so call
+/// `flush` to emit the buffered data and `shutdown` or `close` to finish the stream.
+///
+/// # Examples:
+///
+/// ```rust
+/// # #[cfg(feature = "tokio")]
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// use tokio::io::{AsyncReadExt, AsyncWriteExt};
+/// use xsnappy::{AsyncFrameEncoder, AsyncFrameDecoder};
+///
+/// let mut enc = AsyncFrameEncoder::new(vec![]);
+/// enc.write_all(b"hello world! hello world!").await.unwrap();
+/// enc.shutdown().await.unwrap();
+/// let framed = enc.into_inner();
+///
+/// let mut dst = vec![];
+/// AsyncFrameDecoder::new(&framed[..]).read_to_end(&mut dst).await.unwrap();
+/// assert_eq!(dst, b"hello world! hello world!");
+/// # });
+/// ```
+pub struct AsyncFrameEncoder<W> {
+    w: W,
+    chunks: ChunkWriter,
+}
+
+impl<W> AsyncFrameEncoder<W> {
+    /// Create an encoder that writes the compressed stream to `w`.
+    pub fn new(w: W) -> AsyncFrameEncoder<W> {
+        AsyncFrameEncoder { w, chunks: ChunkWriter::new() }
+    }
+
+    /// Return the underlying writer. Data not flushed yet is lost.
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+
+    /// Return a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.w
+    }
+
+    /// Return a mutable reference to the underlying writer.
+    /// Writing to it directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.w
+    }
+
+    /// Write out the compressed chunk, if any, with `write`.
+    fn poll_pending<F>(&mut self, cx: &mut Context<'_>, mut write: F) -> Poll<io::Result<()>>
+        where F: FnMut(Pin<&mut W>, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>, W: Unpin {
+        while !self.chunks.pending().is_empty() {
+            let n = ready!(write(Pin::new(&mut self.w), cx, self.chunks.pending()))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()))
+            }
+            self.chunks.consume(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_write_with<F>(&mut self, cx: &mut Context<'_>, p: &[u8], write: F) -> Poll<io::Result<usize>>
+        where F: FnMut(Pin<&mut W>, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>, W: Unpin {
+        ready!(self.poll_pending(cx, write))?;
+        if p.is_empty() {
+            return Poll::Ready(Ok(0))
+        }
+        Poll::Ready(Ok(self.chunks.write(p)))
+    }
+}
+
+/// An async reader that decompresses a stream in the Snappy framing format.
+///
+/// It implements tokio's `AsyncRead` and `AsyncBufRead` if `R` implements
+/// `AsyncRead` and the `tokio` feature is enabled, and the futures-io ones
+/// if the `futures-io` feature is enabled. It checks the stream like `FrameDecoder`.
+pub struct AsyncFrameDecoder<R> {
+    r: R,
+    chunks: ChunkReader,
+}
+
+impl<R> AsyncFrameDecoder<R> {
+    /// Create a decoder that reads the compressed stream from `r`.
+    pub fn new(r: R) -> AsyncFrameDecoder<R> {
+        AsyncFrameDecoder::with_options(r, DecodeOptions::default())
+    }
+
+    /// Create a decoder that rejects data beyond the limits of `options`,
+    /// like `FrameDecoder::with_options`.
+    pub fn with_options(r: R, options: DecodeOptions) -> AsyncFrameDecoder<R> {
+        AsyncFrameDecoder { r, chunks: ChunkReader::new(options) }
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+
+    /// Return a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.r
+    }
+
+    /// Return a mutable reference to the underlying reader.
+    /// Reading from it directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.r
+    }
+
+    /// Read chunks with `read` until there is decoded data or the stream ends.
+    fn poll_fill_with<F>(&mut self, cx: &mut Context<'_>, mut read: F) -> Poll<io::Result<&[u8]>>
+        where F: FnMut(Pin<&mut R>, &mut Context<'_>, &mut [u8]) -> Poll<io::Result<usize>>, R: Unpin {
+        while self.chunks.data().is_empty() {
+            let n = ready!(read(Pin::new(&mut self.r), cx, self.chunks.input()))?;
+            self.chunks.advance(n)?;
+            if n == 0 {
+                break
+            }
+        }
+        Poll::Ready(Ok(self.chunks.data()))
+    }
+
+    /// Copy decoded data to `p`, reading chunks with `read` if there is none.
+    fn poll_read_with<F>(&mut self, cx: &mut Context<'_>, p: &mut [u8], read: F) -> Poll<io::Result<usize>>
+        where F: FnMut(Pin<&mut R>, &mut Context<'_>, &mut [u8]) -> Poll<io::Result<usize>>, R: Unpin {
+        let data = ready!(self.poll_fill_with(cx, read))?;
+        let n = std::cmp::min(p.len(), data.len());
+        p[..n].copy_from_slice(&data[..n]);
+        self.chunks.consume(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_impl {
+    use super::{AsyncFrameDecoder, AsyncFrameEncoder};
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{ready, Context, Poll};
+    use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, ReadBuf};
+
+    fn read<R: AsyncRead>(r: Pin<&mut R>, cx: &mut Context<'_>, p: &mut [u8]) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(p);
+        ready!(r.poll_read(cx, &mut buf))?;
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+
+    impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncFrameEncoder<W> {
+        fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, p: &[u8]) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write_with(cx, p, |w, cx, p| w.poll_write(cx, p))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_pending(cx, |w, cx, p| w.poll_write(cx, p)))?;
+            this.chunks.flush();
+            ready!(this.poll_pending(cx, |w, cx, p| w.poll_write(cx, p)))?;
+            Pin::new(&mut this.w).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            ready!(self.as_mut().poll_flush(cx))?;
+            Pin::new(&mut self.get_mut().w).poll_shutdown(cx)
+        }
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncRead for AsyncFrameDecoder<R> {
+        fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            let n = ready!(this.poll_read_with(cx, buf.initialize_unfilled(), read))?;
+            buf.advance(n);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncBufRead for AsyncFrameDecoder<R> {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+            self.get_mut().poll_fill_with(cx, read)
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            self.get_mut().chunks.consume(amt);
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+mod futures_impl {
+    use super::{AsyncFrameDecoder, AsyncFrameEncoder};
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{ready, Context, Poll};
+    use futures_io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+    impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncFrameEncoder<W> {
+        fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, p: &[u8]) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write_with(cx, p, |w, cx, p| w.poll_write(cx, p))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_pending(cx, |w, cx, p| w.poll_write(cx, p)))?;
+            this.chunks.flush();
+            ready!(this.poll_pending(cx, |w, cx, p| w.poll_write(cx, p)))?;
+            Pin::new(&mut this.w).poll_flush(cx)
+        }
+
+        fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            ready!(self.as_mut().poll_flush(cx))?;
+            Pin::new(&mut self.get_mut().w).poll_close(cx)
+        }
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncRead for AsyncFrameDecoder<R> {
+        fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, p: &mut [u8]) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_read_with(cx, p, |r, cx, p| r.poll_read(cx, p))
+        }
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncBufRead for AsyncFrameDecoder<R> {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+            self.get_mut().poll_fill_with(cx, |r, cx, p| r.poll_read(cx, p))
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            self.get_mut().chunks.consume(amt);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! The state machines of the framing format, without any IO, shared by
+//! the blocking and the async encoders and decoders.
+
+use crate::frame::{check_chunk, compress_chunk, corrupt, decode_chunk, parse_chunk_header, CHECKSUM_SIZE,
+                   CHUNK_HEADER_SIZE, CHUNK_PREFIX_LEN, CHUNK_TYPE_STREAM_IDENTIFIER, MAGIC_CHUNK, MAX_BLOCK_SIZE,
+                   MAX_ENCODED_LEN_OF_MAX_BLOCK_SIZE};
+use crate::decode::DecodeOptions;
+use crate::error::{SnappyError, CorruptKind};
+
+/// Buffers written data and compresses it to chunks of at most 64 KiB,
+/// the same way as `NewBufferedWriter` of the Go package.
+pub(crate) struct ChunkWriter {
+    ibuf: Vec<u8>,
+    obuf: Vec<u8>,
+    start: usize,
+    end: usize,
+    wrote_stream_header: bool,
+}
+
+impl ChunkWriter {
+    pub(crate) fn new() -> ChunkWriter {
+        ChunkWriter {
+            ibuf: Vec::with_capacity(MAX_BLOCK_SIZE),
+            // Room for two chunks, see `write`.
+            obuf: vec![0; MAGIC_CHUNK.len() + 2 * (CHUNK_PREFIX_LEN + MAX_ENCODED_LEN_OF_MAX_BLOCK_SIZE)],
+            start: 0,
+            end: 0,
+            wrote_stream_header: false,
+        }
+    }
+
+    /// Discard any buffered data and start a new stream.
+    pub(crate) fn reset(&mut self) {
+        self.ibuf.clear();
+        self.start = 0;
+        self.end = 0;
+        self.wrote_stream_header = false;
+    }
+
+    /// Return the compressed data waiting to be written out.
+    pub(crate) fn pending(&self) -> &[u8] {
+        &self.obuf[self.start..self.end]
+    }
+
+    /// Mark `n` bytes of the pending data as written.
+    pub(crate) fn consume(&mut self, n: usize) {
+        self.start += n;
+    }
+
+    /// Accept a prefix of `p`, which must not be empty, and return its length.
+    /// It may compress chunks, which must be written out before the next call.
+    pub(crate) fn write(&mut self, p: &[u8]) -> usize {
+        debug_assert!(self.pending().is_empty());
+        if self.ibuf.is_empty() && p.len() > MAX_BLOCK_SIZE {
+            // Large write, empty buffer. Compress directly from p to avoid copy,
+            // the last chunk included. A chunk at a time, but the last two together,
+            // so that the rest of `p` is never left to the buffer.
+            self.compress(&p[..MAX_BLOCK_SIZE]);
+            if p.len() > 2 * MAX_BLOCK_SIZE {
+                return MAX_BLOCK_SIZE
+            }
+            self.compress(&p[MAX_BLOCK_SIZE..]);
+            return p.len()
+        }
+        let n = MAX_BLOCK_SIZE - self.ibuf.len();
+        if p.len() <= n {
+            self.ibuf.extend_from_slice(p);
+            return p.len()
+        }
+        self.ibuf.extend_from_slice(&p[..n]);
+        self.flush();
+        n
+    }
+
+    /// Compress the buffered data, if any, to a chunk that must be written out.
+    pub(crate) fn flush(&mut self) {
+        debug_assert!(self.pending().is_empty());
+        if !self.ibuf.is_empty() {
+            let ibuf = std::mem::take(&mut self.ibuf);
+            self.compress(&ibuf);
+            self.ibuf = ibuf;
+            self.ibuf.clear();
+        }
+    }
+
+    /// Compress `src` to a chunk after the pending data.
+    fn compress(&mut self, src: &[u8]) {
+        if self.pending().is_empty() {
+            self.start = MAGIC_CHUNK.len();
+            self.end = MAGIC_CHUNK.len();
+            if !self.wrote_stream_header {
+                self.wrote_stream_header = true;
+                self.obuf[..MAGIC_CHUNK.len()].copy_from_slice(MAGIC_CHUNK);
+                self.start = 0;
+            }
+        }
+        self.end += compress_chunk(&mut self.obuf[self.end..], src);
+    }
+}
+
+/// What a `ChunkReader` reads next.
+#[derive(Clone, Copy)]
+enum ReadState {
+    Header,
+    Body { chunk_type: u8, len: usize },
+    Skip { len: usize, remaining: usize },
+}
+
+/// Parses chunks fed to it and decodes them.
+pub(crate) struct ChunkReader {
+    header: [u8; CHUNK_HEADER_SIZE],
+    buf: Vec<u8>,
+    filled: usize,
+    state: ReadState,
+    decoded: Vec<u8>,
+    i: usize,
+    j: usize,
+    read_stream_header: bool,
+    options: DecodeOptions,
+    decoded_len: usize,
+    /// The position in the stream of the current chunk.
+    pos: usize,
+}
+
+impl ChunkReader {
+    pub(crate) fn new(options: DecodeOptions) -> ChunkReader {
+        ChunkReader {
+            header: [0; CHUNK_HEADER_SIZE],
+            buf: vec![0; CHECKSUM_SIZE + MAX_ENCODED_LEN_OF_MAX_BLOCK_SIZE],
+            filled: 0,
+            state: ReadState::Header,
+            decoded: vec![0; MAX_BLOCK_SIZE],
+            i: 0,
+            j: 0,
+            read_stream_header: false,
+            options,
+            decoded_len: 0,
+            pos: 0,
+        }
+    }
+
+    /// Discard any buffered data and start a new stream.
+    pub(crate) fn reset(&mut self) {
+        self.filled = 0;
+        self.state = ReadState::Header;
+        self.i = 0;
+        self.j = 0;
+        self.read_stream_header = false;
+        self.decoded_len = 0;
+        self.pos = 0;
+    }
+
+    /// Return the decoded data not consumed yet.
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.decoded[self.i..self.j]
+    }
+
+    /// Mark `amt` bytes of the decoded data as consumed.
+    pub(crate) fn consume(&mut self, amt: usize) {
+        self.i = std::cmp::min(self.i + amt, self.j);
+    }
+
+    /// Return the buffer to read the next input into. It is never empty.
+    pub(crate) fn input(&mut self) -> &mut [u8] {
+        match self.state {
+            ReadState::Header => &mut self.header[self.filled..],
+            ReadState::Body { len, .. } => &mut self.buf[self.filled..len],
+            ReadState::Skip { remaining, .. } => {
+                let n = std::cmp::min(remaining, self.buf.len());
+                &mut self.buf[..n]
+            }
+        }
+    }
+
+    /// Process `n` bytes read into `input`. The input ended if `n` is 0,
+    /// which is an error unless it ended between chunks.
+    pub(crate) fn advance(&mut self, n: usize) -> Result<(), SnappyError> {
+        if n == 0 {
+            return match self.state {
+                ReadState::Header if self.filled == 0 => Ok(()),
+                _ => Err(self.at(corrupt(CorruptKind::TruncatedChunk)))
+            }
+        }
+        match self.state {
+            ReadState::Header => {
+                self.filled += n;
+                if self.filled < CHUNK_HEADER_SIZE {
+                    return Ok(())
+                }
+                self.filled = 0;
+                let (chunk_type, len) = parse_chunk_header(&self.header);
+                if check_chunk(chunk_type, len, self.read_stream_header).map_err(|err| self.at(err))? {
+                    self.state = ReadState::Body { chunk_type, len };
+                } else {
+                    self.skip(len, len);
+                }
+            }
+            ReadState::Body { chunk_type, len } => {
+                self.filled += n;
+                if self.filled < len {
+                    return Ok(())
+                }
+                let body = &self.buf[..len];
+                let m = decode_chunk(chunk_type, body, &mut self.decoded, &self.options).map_err(|err| self.at(err))?;
+                self.decoded_len = self.decoded_len.saturating_add(m);
+                self.options.check_len(self.decoded_len)?;
+                if chunk_type == CHUNK_TYPE_STREAM_IDENTIFIER {
+                    self.read_stream_header = true;
+                }
+                self.i = 0;
+                self.j = m;
+                self.next_chunk(len);
+            }
+            ReadState::Skip { len, remaining } => self.skip(len, remaining - n)
+        }
+        Ok(())
+    }
+
+    /// Skip the last `remaining` bytes of the current chunk of `len` bytes.
+    fn skip(&mut self, len: usize, remaining: usize) {
+        if remaining == 0 {
+            self.next_chunk(len);
+        } else {
+            self.state = ReadState::Skip { len, remaining };
+        }
+    }
+
+    /// Move past the current chunk of `len` bytes after its header.
+    fn next_chunk(&mut self, len: usize) {
+        self.filled = 0;
+        self.state = ReadState::Header;
+        self.pos += CHUNK_HEADER_SIZE + len;
+    }
+
+    /// Move an error of the current chunk to its position in the stream.
+    fn at(&self, err: SnappyError) -> SnappyError {
+        err.shift(self.pos, self.decoded_len)
+    }
+}
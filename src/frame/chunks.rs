@@ -0,0 +1,139 @@
+//! An iterator over the chunks of a framed stream held in memory.
+
+use crate::frame::{check_chunk, corrupt, decode_chunk_crc, parse_chunk_header, CHUNK_HEADER_SIZE, CHUNK_TYPE_COMPRESSED_DATA,
+                   CHUNK_TYPE_PADDING, CHUNK_TYPE_STREAM_IDENTIFIER, CHUNK_TYPE_UNCOMPRESSED_DATA, MAX_BLOCK_SIZE};
+use crate::decode::DecodeOptions;
+use crate::error::{SnappyError, CorruptKind};
+
+/// The type of a chunk of the framing format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkType {
+    /// Compressed data (chunk type 0x00).
+    Compressed,
+    /// Uncompressed data (chunk type 0x01).
+    Uncompressed,
+    /// Padding (chunk type 0xfe).
+    Padding,
+    /// A reserved skippable chunk (chunk types 0x80-0xfd).
+    Skippable(u8),
+    /// The stream identifier (chunk type 0xff).
+    StreamIdentifier,
+}
+
+/// A chunk of a framed stream, yielded by `Chunks`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk<'a> {
+    /// The position of the chunk in the stream.
+    pub offset: usize,
+    /// The type of the chunk.
+    pub chunk_type: ChunkType,
+    /// The body of the chunk, after its 4-byte header. The body of a data chunk
+    /// starts with the checksum.
+    pub body: &'a [u8],
+    /// The length of the data the chunk decodes to, 0 for chunks without data.
+    pub decoded_len: usize,
+    /// Whether the checksum of the data matches, true for chunks without data.
+    /// Only false after `ignore_checksums`.
+    pub crc_ok: bool,
+}
+
+/// An iterator over the chunks of a framed stream, created by `chunks`.
+///
+/// It checks every chunk the way `FrameDecoder` does: the stream must start with
+/// the stream identifier, data chunks are decoded to check their checksums,
+/// and reserved unskippable chunks are errors. Iteration stops at the first error,
+/// which is at its position in the stream. After `ignore_checksums`, a checksum
+/// mismatch is reported by `Chunk::crc_ok` instead, and iteration goes on.
+pub struct Chunks<'a> {
+    src: &'a [u8],
+    pos: usize,
+    decoded: Vec<u8>,
+    decoded_len: usize,
+    read_stream_header: bool,
+    verify_checksums: bool,
+    done: bool,
+}
+
+impl<'a> Chunks<'a> {
+    pub(crate) fn new(src: &'a [u8]) -> Chunks<'a> {
+        Chunks { src, pos: 0, decoded: vec![], decoded_len: 0, read_stream_header: false, verify_checksums: true, done: false }
+    }
+
+    /// Yield the chunks whose checksums don't match, with `crc_ok` false, instead
+    /// of failing at the first one. Other errors still stop the iteration.
+    pub fn ignore_checksums(mut self) -> Chunks<'a> {
+        self.verify_checksums = false;
+        self
+    }
+
+    /// Return the position in the stream of the next chunk, or of the chunk
+    /// that failed.
+    pub fn input_offset(&self) -> usize {
+        self.pos
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<Chunk<'a>>, SnappyError> {
+        let src = &self.src[self.pos..];
+        if src.is_empty() {
+            return Ok(None)
+        }
+        if src.len() < CHUNK_HEADER_SIZE {
+            return Err(corrupt(CorruptKind::TruncatedChunk))
+        }
+        let (chunk_type, len) = parse_chunk_header(src);
+        let decode = check_chunk(chunk_type, len, self.read_stream_header)?;
+        if src.len() - CHUNK_HEADER_SIZE < len {
+            return Err(corrupt(CorruptKind::TruncatedChunk))
+        }
+        let body = &src[CHUNK_HEADER_SIZE..CHUNK_HEADER_SIZE + len];
+        let (mut decoded_len, mut crc_ok) = (0, true);
+        if decode {
+            if self.decoded.is_empty() {
+                self.decoded = vec![0; MAX_BLOCK_SIZE];
+            }
+            let (n, ok) = decode_chunk_crc(chunk_type, body, &mut self.decoded, &DecodeOptions::default())?;
+            if !ok && self.verify_checksums {
+                return Err(corrupt(CorruptKind::Checksum))
+            }
+            decoded_len = n;
+            crc_ok = ok;
+        }
+        let chunk_type = match chunk_type {
+            CHUNK_TYPE_COMPRESSED_DATA => ChunkType::Compressed,
+            CHUNK_TYPE_UNCOMPRESSED_DATA => ChunkType::Uncompressed,
+            CHUNK_TYPE_PADDING => ChunkType::Padding,
+            CHUNK_TYPE_STREAM_IDENTIFIER => {
+                self.read_stream_header = true;
+                ChunkType::StreamIdentifier
+            }
+            t => ChunkType::Skippable(t)
+        };
+        let chunk = Chunk { offset: self.pos, chunk_type, body, decoded_len, crc_ok };
+        self.pos += CHUNK_HEADER_SIZE + len;
+        self.decoded_len += decoded_len;
+        Ok(Some(chunk))
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<Chunk<'a>, SnappyError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None
+        }
+        match self.next_chunk() {
+            Ok(Some(chunk)) => Some(Ok(chunk)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err.shift(self.pos, self.decoded_len)))
+            }
+        }
+    }
+}
+
+impl<'a> core::iter::FusedIterator for Chunks<'a> {}
@@ -0,0 +1,99 @@
+use crate::frame::chunk::ChunkReader;
+use crate::decode::DecodeOptions;
+use std::io::{self, Read, BufRead};
+
+/// A reader that decompresses a stream in the Snappy framing format.
+///
+/// Every chunk is verified against its checksum. Padding and reserved
+/// skippable chunks are skipped, reserved unskippable chunks are rejected
+/// with `SnappyError::UnskippableChunk`.
+///
+/// # Examples:
+///
+/// ```rust
+/// use std::io::{Read, Write};
+/// use xsnappy::{FrameEncoder, FrameDecoder};
+///
+/// let mut enc = FrameEncoder::new(vec![]);
+/// enc.write_all(b"hello world! hello world!").unwrap();
+/// let framed = enc.into_inner().unwrap();
+///
+/// let mut dst = vec![];
+/// FrameDecoder::new(&framed[..]).read_to_end(&mut dst).unwrap();
+/// assert_eq!(dst, b"hello world! hello world!");
+/// ```
+pub struct FrameDecoder<R: Read> {
+    r: R,
+    chunks: ChunkReader,
+}
+
+impl<R: Read> FrameDecoder<R> {
+    /// Create a decoder that reads the compressed stream from `r`.
+    pub fn new(r: R) -> FrameDecoder<R> {
+        FrameDecoder::with_options(r, DecodeOptions::default())
+    }
+
+    /// Create a decoder that fails with `SnappyError::LimitExceeded` once the stream
+    /// decodes to more than `max_decoded_len` bytes in total, or a compressed chunk
+    /// exceeds `max_ratio`.
+    pub fn with_options(r: R, options: DecodeOptions) -> FrameDecoder<R> {
+        FrameDecoder { r, chunks: ChunkReader::new(options) }
+    }
+
+    /// Discard any buffered data and switch to reading a new stream from `r`.
+    /// Return the previous reader.
+    pub fn reset(&mut self, r: R) -> R {
+        self.chunks.reset();
+        std::mem::replace(&mut self.r, r)
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+
+    /// Return a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.r
+    }
+
+    /// Return a mutable reference to the underlying reader.
+    /// Reading from it directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.r
+    }
+}
+
+impl<R: Read> Read for FrameDecoder<R> {
+    fn read(&mut self, p: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let data = self.fill_buf()?;
+            let n = std::cmp::min(p.len(), data.len());
+            p[..n].copy_from_slice(&data[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for FrameDecoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.chunks.data().is_empty() {
+            let n = match self.r.read(self.chunks.input()) {
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e)
+            };
+            self.chunks.advance(n)?;
+            if n == 0 {
+                break
+            }
+        }
+        Ok(self.chunks.data())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.chunks.consume(amt);
+    }
+}
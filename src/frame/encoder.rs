@@ -0,0 +1,121 @@
+use crate::frame::chunk::ChunkWriter;
+use std::io::{self, Write};
+
+/// A writer that compresses data to the Snappy framing format.
+///
+/// Written data is buffered and emitted as chunks of at most 64 KiB of
+/// uncompressed data, the same way as `NewBufferedWriter` of the Go package.
+/// Call `flush` to emit the buffered data. The buffered data is also flushed
+/// when the encoder is dropped, but errors are ignored there.
+///
+/// # Examples:
+///
+/// ```rust
+/// use std::io::Write;
+/// use xsnappy::FrameEncoder;
+///
+/// let mut enc = FrameEncoder::new(vec![]);
+/// enc.write_all(b"hello world! hello world!").unwrap();
+/// let framed = enc.into_inner().unwrap();
+/// assert_eq!(&framed[..10], b"\xff\x06\x00\x00sNaPpY");
+/// ```
+pub struct FrameEncoder<W: Write> {
+    w: Option<W>,
+    err: Option<io::Error>,
+    chunks: ChunkWriter,
+}
+
+impl<W: Write> FrameEncoder<W> {
+    /// Create an encoder that writes the compressed stream to `w`.
+    pub fn new(w: W) -> FrameEncoder<W> {
+        FrameEncoder {
+            w: Some(w),
+            err: None,
+            chunks: ChunkWriter::new(),
+        }
+    }
+
+    /// Discard any buffered data, clear any error and switch to writing a
+    /// new stream to `w`. Return the previous writer.
+    pub fn reset(&mut self, w: W) -> W {
+        self.err = None;
+        self.chunks.reset();
+        self.w.replace(w).unwrap()
+    }
+
+    /// Flush the buffered data and return the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.w.take().unwrap())
+    }
+
+    /// Return a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.w.as_ref().unwrap()
+    }
+
+    /// Return a mutable reference to the underlying writer.
+    /// Writing to it directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.w.as_mut().unwrap()
+    }
+
+    /// Write out the compressed chunk, if any. A failure is kept and returned
+    /// by every later call.
+    fn write_pending(&mut self) -> io::Result<()> {
+        self.check_err()?;
+        let pending = self.chunks.pending();
+        if pending.is_empty() {
+            return Ok(())
+        }
+        let n = pending.len();
+        if let Err(err) = self.w.as_mut().unwrap().write_all(pending) {
+            let ret = io::Error::new(err.kind(), err.to_string());
+            self.err = Some(err);
+            return Err(ret);
+        }
+        self.chunks.consume(n);
+        Ok(())
+    }
+
+    /// Return the error of an earlier failed write, if any.
+    fn check_err(&self) -> io::Result<()> {
+        match &self.err {
+            Some(err) => Err(io::Error::new(err.kind(), err.to_string())),
+            None => Ok(())
+        }
+    }
+
+    /// Emit the buffered data as chunks.
+    fn flush_buf(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.chunks.flush();
+        self.write_pending()
+    }
+}
+
+impl<W: Write> Write for FrameEncoder<W> {
+    fn write(&mut self, p: &[u8]) -> io::Result<usize> {
+        let mut n = 0;
+        self.write_pending()?;
+        while n < p.len() {
+            n += self.chunks.write(&p[n..]);
+            self.write_pending()?;
+        }
+        Ok(n)
+    }
+
+    /// Emit the buffered data as chunks and flush the underlying writer.
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf()?;
+        self.w.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for FrameEncoder<W> {
+    fn drop(&mut self) {
+        if self.w.is_some() {
+            let _ = self.flush_buf();
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! The Snappy framing format, described at
+//! <https://github.com/google/snappy/blob/master/framing_format.txt>.
+
+mod chunk;
+mod chunks;
+mod encoder;
+mod decoder;
+mod pool;
+mod parallel_encoder;
+mod parallel_decoder;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod async_io;
+
+pub use chunks::{Chunks, Chunk, ChunkType};
+pub use encoder::FrameEncoder;
+pub use decoder::FrameDecoder;
+pub use parallel_encoder::ParallelFrameEncoder;
+pub use parallel_decoder::ParallelFrameDecoder;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub use async_io::{AsyncFrameEncoder, AsyncFrameDecoder};
+
+use crate::crc32c::{crc32c, mask};
+use crate::encode::encode;
+use crate::decode::{decode, decode_len, DecodeOptions};
+use crate::error::{SnappyError, CorruptKind};
+
+pub(crate) const CHUNK_TYPE_COMPRESSED_DATA: u8 = 0x00;
+pub(crate) const CHUNK_TYPE_UNCOMPRESSED_DATA: u8 = 0x01;
+pub(crate) const CHUNK_TYPE_PADDING: u8 = 0xfe;
+pub(crate) const CHUNK_TYPE_STREAM_IDENTIFIER: u8 = 0xff;
+
+pub(crate) const CHECKSUM_SIZE: usize = 4;
+pub(crate) const CHUNK_HEADER_SIZE: usize = 4;
+
+pub(crate) const MAGIC_BODY: &[u8] = b"sNaPpY";
+pub(crate) const MAGIC_CHUNK: &[u8] = b"\xff\x06\x00\x00sNaPpY";
+
+/// The maximum size of the uncompressed data in a single chunk.
+pub(crate) const MAX_BLOCK_SIZE: usize = 65536;
+
+/// The result of `max_encode_len(MAX_BLOCK_SIZE)`.
+pub(crate) const MAX_ENCODED_LEN_OF_MAX_BLOCK_SIZE: usize = 76490;
+
+/// The length of a chunk header plus its checksum.
+pub(crate) const CHUNK_PREFIX_LEN: usize = CHUNK_HEADER_SIZE + CHECKSUM_SIZE;
+
+/// Write one chunk holding `src` to `dst`, header and checksum included.
+/// The chunk is stored uncompressed if compressing it doesn't save at least 12.5%.
+/// `src` must not be longer than `MAX_BLOCK_SIZE` and `dst` must be at least
+/// `CHUNK_PREFIX_LEN + MAX_ENCODED_LEN_OF_MAX_BLOCK_SIZE` long.
+/// Return the length of the chunk.
+pub(crate) fn compress_chunk(dst: &mut [u8], src: &[u8]) -> usize {
+    let checksum = mask(crc32c(src));
+    let mut n = encode(&mut dst[CHUNK_PREFIX_LEN..], src);
+    let mut chunk_type = CHUNK_TYPE_COMPRESSED_DATA;
+    if n >= src.len() - src.len() / 8 {
+        chunk_type = CHUNK_TYPE_UNCOMPRESSED_DATA;
+        n = src.len();
+        dst[CHUNK_PREFIX_LEN..CHUNK_PREFIX_LEN + n].copy_from_slice(src);
+    }
+    put_chunk_header(dst, chunk_type, CHECKSUM_SIZE + n);
+    dst[4..8].copy_from_slice(&checksum.to_le_bytes());
+    CHUNK_PREFIX_LEN + n
+}
+
+/// Write the chunk type and the 24-bit little-endian chunk length to `dst`.
+pub(crate) fn put_chunk_header(dst: &mut [u8], chunk_type: u8, chunk_len: usize) {
+    dst[0] = chunk_type;
+    dst[1] = chunk_len as u8;
+    dst[2] = (chunk_len >> 8) as u8;
+    dst[3] = (chunk_len >> 16) as u8;
+}
+
+/// Return the chunk type and the chunk length of a chunk header.
+pub(crate) fn parse_chunk_header(header: &[u8]) -> (u8, usize) {
+    (header[0], header[1] as usize | (header[2] as usize) << 8 | (header[3] as usize) << 16)
+}
+
+/// Check a chunk header against the framing format.
+/// `read_stream_header` tells whether the stream identifier has been read.
+/// Return true if the chunk body should be passed to `decode_chunk`,
+/// false if it should be skipped.
+pub(crate) fn check_chunk(chunk_type: u8, chunk_len: usize, read_stream_header: bool) -> Result<bool, SnappyError> {
+    if !read_stream_header && chunk_type != CHUNK_TYPE_STREAM_IDENTIFIER {
+        return Err(corrupt(CorruptKind::StreamIdentifier))
+    }
+    match chunk_type {
+        CHUNK_TYPE_COMPRESSED_DATA => {
+            if !(CHECKSUM_SIZE..=CHECKSUM_SIZE + MAX_ENCODED_LEN_OF_MAX_BLOCK_SIZE).contains(&chunk_len) {
+                return Err(corrupt(CorruptKind::ChunkLength))
+            }
+        }
+        CHUNK_TYPE_UNCOMPRESSED_DATA => {
+            if !(CHECKSUM_SIZE..=CHECKSUM_SIZE + MAX_BLOCK_SIZE).contains(&chunk_len) {
+                return Err(corrupt(CorruptKind::ChunkLength))
+            }
+        }
+        CHUNK_TYPE_STREAM_IDENTIFIER => {
+            if chunk_len != MAGIC_BODY.len() {
+                return Err(corrupt(CorruptKind::ChunkLength))
+            }
+        }
+        // Reserved unskippable chunks (chunk types 0x02-0x7f).
+        0x02..=0x7f => return Err(SnappyError::UnskippableChunk),
+        // Padding (chunk type 0xfe) and reserved skippable chunks (chunk types 0x80-0xfd).
+        CHUNK_TYPE_PADDING | 0x80..=0xfd => return Ok(false)
+    }
+    Ok(true)
+}
+
+/// Decode the body of a chunk accepted by `check_chunk` to `dst`,
+/// which must be at least `MAX_BLOCK_SIZE` long. Compressed data is checked
+/// against the ratio limit of `options`. Errors are relative to the start of the chunk.
+/// Return the length of the decoded data, 0 for a stream identifier.
+pub(crate) fn decode_chunk(chunk_type: u8, body: &[u8], dst: &mut [u8], options: &DecodeOptions) -> Result<usize, SnappyError> {
+    match decode_chunk_crc(chunk_type, body, dst, options)? {
+        (n, true) => Ok(n),
+        (_, false) => Err(corrupt(CorruptKind::Checksum))
+    }
+}
+
+/// Decode a chunk like `decode_chunk`, but return whether the checksum matches
+/// along with the decoded length instead of failing on a mismatch.
+pub(crate) fn decode_chunk_crc(chunk_type: u8, body: &[u8], dst: &mut [u8], options: &DecodeOptions) -> Result<(usize, bool), SnappyError> {
+    if chunk_type == CHUNK_TYPE_STREAM_IDENTIFIER {
+        if body != MAGIC_BODY {
+            return Err(corrupt(CorruptKind::StreamIdentifier))
+        }
+        return Ok((0, true))
+    }
+    let checksum = u32::from_le_bytes([body[0], body[1], body[2], body[3]]);
+    let body = &body[CHECKSUM_SIZE..];
+    let n;
+    if chunk_type == CHUNK_TYPE_COMPRESSED_DATA {
+        n = decode_len(body).map_err(|err| err.shift(CHUNK_PREFIX_LEN, 0))?;
+        if n > MAX_BLOCK_SIZE {
+            return Err(corrupt(CorruptKind::ChunkLength))
+        }
+        options.check_ratio(n, body.len())?;
+        decode(&mut dst[..n], body).map_err(|err| err.shift(CHUNK_PREFIX_LEN, 0))?;
+    } else {
+        n = body.len();
+        dst[..n].copy_from_slice(body);
+    }
+    Ok((n, mask(crc32c(&dst[..n])) == checksum))
+}
+
+/// Return a corruption of `kind` in the chunk at input offset 0.
+/// Errors of a chunk are relative to its start, and the readers shift them
+/// to the position of the chunk in the stream.
+pub(crate) fn corrupt(kind: CorruptKind) -> SnappyError {
+    SnappyError::corrupt(kind, 0, 0, None)
+}
@@ -0,0 +1,250 @@
+use crate::frame::pool::{join, Pool};
+use crate::frame::{check_chunk, corrupt, decode_chunk, parse_chunk_header, CHUNK_HEADER_SIZE,
+                   CHUNK_TYPE_STREAM_IDENTIFIER, MAX_BLOCK_SIZE};
+use crate::stream::read_full;
+use crate::decode::DecodeOptions;
+use crate::error::{SnappyError, CorruptKind};
+use std::collections::VecDeque;
+use std::io::{self, Read, BufRead};
+use std::sync::mpsc::Receiver;
+
+/// The body and the decoded data of a chunk, with the decoded length.
+type Decoded = (Vec<u8>, Vec<u8>, usize);
+
+/// A chunk read ahead, in stream order.
+enum Slot {
+    /// A data chunk at input offset `pos`, being decoded.
+    Chunk { pos: usize, rx: Receiver<Result<Decoded, SnappyError>> },
+    /// A corrupt chunk, at its input offset.
+    Corrupt(SnappyError),
+    /// A failed read of the underlying reader.
+    Io(io::Error),
+}
+
+/// A reader that decompresses a stream in the Snappy framing format on a pool of threads.
+///
+/// It reads ahead up to `read_ahead` data chunks, decodes and verifies them on
+/// the threads, and returns their data in order. It accepts and rejects the
+/// same streams as `FrameDecoder`, with the same errors, which are returned
+/// once the data before them has been read.
+/// A data chunk takes at most about 140 KiB while in flight, so the memory is
+/// bounded by `read_ahead` times that.
+///
+/// # Examples:
+///
+/// ```rust
+/// use std::io::{Read, Write};
+/// use xsnappy::{FrameEncoder, ParallelFrameDecoder};
+///
+/// let src = b"hello world! ".repeat(100000);
+/// let mut enc = FrameEncoder::new(vec![]);
+/// enc.write_all(&src).unwrap();
+/// let framed = enc.into_inner().unwrap();
+///
+/// let mut dst = vec![];
+/// ParallelFrameDecoder::new(&framed[..], 4).read_to_end(&mut dst).unwrap();
+/// assert_eq!(dst, src);
+/// ```
+pub struct ParallelFrameDecoder<R: Read> {
+    r: R,
+    err: Option<io::Error>,
+    pool: Pool,
+    options: DecodeOptions,
+    read_ahead: usize,
+    in_flight: VecDeque<Slot>,
+    /// Buffers to reuse.
+    spare: Vec<Vec<u8>>,
+    /// The decoded data of the current chunk, and the part not consumed yet.
+    decoded: Vec<u8>,
+    i: usize,
+    j: usize,
+    /// Whether the underlying reader ended or failed.
+    done: bool,
+    read_stream_header: bool,
+    /// The input offset of the next chunk to read.
+    pos: usize,
+    /// The length of the data decoded so far.
+    decoded_len: usize,
+}
+
+impl<R: Read> ParallelFrameDecoder<R> {
+    /// Create a decoder that reads the compressed stream from `r` and decodes it on
+    /// `threads` threads, or on as many as the available parallelism if `threads` is 0.
+    /// It reads ahead two chunks per thread.
+    pub fn new(r: R, threads: usize) -> ParallelFrameDecoder<R> {
+        let pool = Pool::new(threads);
+        let read_ahead = 2 * pool.threads();
+        ParallelFrameDecoder::with_pool(r, pool, read_ahead, DecodeOptions::default())
+    }
+
+    /// Create a decoder like `new` that reads ahead at most `read_ahead` data chunks,
+    /// and rejects data beyond the limits of `options` like `FrameDecoder::with_options`.
+    pub fn with_options(r: R, threads: usize, read_ahead: usize, options: DecodeOptions) -> ParallelFrameDecoder<R> {
+        ParallelFrameDecoder::with_pool(r, Pool::new(threads), read_ahead, options)
+    }
+
+    fn with_pool(r: R, pool: Pool, read_ahead: usize, options: DecodeOptions) -> ParallelFrameDecoder<R> {
+        ParallelFrameDecoder {
+            r,
+            err: None,
+            pool,
+            options,
+            read_ahead: std::cmp::max(read_ahead, 1),
+            in_flight: VecDeque::new(),
+            spare: Vec::new(),
+            decoded: Vec::new(),
+            i: 0,
+            j: 0,
+            done: false,
+            read_stream_header: false,
+            pos: 0,
+            decoded_len: 0,
+        }
+    }
+
+    /// Return the underlying reader. Chunks read ahead are lost.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+
+    /// Return a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.r
+    }
+
+    /// Return a mutable reference to the underlying reader.
+    /// Reading from it directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.r
+    }
+
+    /// Return the error of an earlier failed read, if any.
+    fn check_err(&self) -> io::Result<()> {
+        match &self.err {
+            Some(err) => Err(copy_err(err)),
+            None => Ok(())
+        }
+    }
+
+    /// Read chunks until `read_ahead` data chunks are in flight or the underlying reader ends.
+    fn read_ahead(&mut self) {
+        while !self.done && self.in_flight.len() < self.read_ahead {
+            match self.read_chunk() {
+                Ok(true) => {}
+                Ok(false) => self.done = true,
+                Err(slot) => {
+                    self.in_flight.push_back(slot);
+                    self.done = true;
+                }
+            }
+        }
+    }
+
+    /// Read the next chunk, and start decoding it if it holds data.
+    /// Return false at the end of the stream.
+    fn read_chunk(&mut self) -> Result<bool, Slot> {
+        let pos = self.pos;
+        let truncated = || Slot::Corrupt(corrupt(CorruptKind::TruncatedChunk).shift(pos, 0));
+        let mut header = [0; CHUNK_HEADER_SIZE];
+        match read_full(&mut self.r, &mut header).map_err(Slot::Io)? {
+            0 => return Ok(false),
+            CHUNK_HEADER_SIZE => {}
+            _ => return Err(truncated())
+        }
+        let (chunk_type, len) = parse_chunk_header(&header);
+        let decode = check_chunk(chunk_type, len, self.read_stream_header)
+            .map_err(|err| Slot::Corrupt(err.shift(pos, 0)))?;
+        self.pos += CHUNK_HEADER_SIZE + len;
+        if !decode {
+            let n = io::copy(&mut (&mut self.r).take(len as u64), &mut io::sink()).map_err(Slot::Io)?;
+            if n < len as u64 {
+                return Err(truncated())
+            }
+            return Ok(true)
+        }
+
+        let mut body = self.spare.pop().unwrap_or_default();
+        body.resize(len, 0);
+        if read_full(&mut self.r, &mut body).map_err(Slot::Io)? < len {
+            return Err(truncated())
+        }
+        if chunk_type == CHUNK_TYPE_STREAM_IDENTIFIER {
+            decode_chunk(chunk_type, &body, &mut [], &self.options).map_err(|err| Slot::Corrupt(err.shift(pos, 0)))?;
+            self.read_stream_header = true;
+            self.spare.push(body);
+            return Ok(true)
+        }
+        let mut decoded = self.spare.pop().unwrap_or_default();
+        let options = self.options;
+        let rx = self.pool.spawn(move || {
+            decoded.resize(MAX_BLOCK_SIZE, 0);
+            let n = decode_chunk(chunk_type, &body, &mut decoded, &options)?;
+            Ok((body, decoded, n))
+        });
+        self.in_flight.push_back(Slot::Chunk { pos, rx });
+        Ok(true)
+    }
+
+    /// Make the next chunk in flight the current one. A failure is kept and
+    /// returned by every later call. Return false at the end of the stream.
+    fn next_chunk(&mut self) -> io::Result<bool> {
+        self.check_err()?;
+        self.read_ahead();
+        let ret = match self.in_flight.pop_front() {
+            None => return Ok(false),
+            Some(Slot::Chunk { pos, rx }) => join(&rx).and_then(|ret| {
+                let (body, decoded, n) = ret.map_err(|err| err.shift(pos, self.decoded_len))?;
+                self.decoded_len = self.decoded_len.saturating_add(n);
+                self.options.check_len(self.decoded_len)?;
+                self.spare.push(body);
+                self.spare.push(std::mem::replace(&mut self.decoded, decoded));
+                self.i = 0;
+                self.j = n;
+                Ok(())
+            }),
+            Some(Slot::Corrupt(err)) => Err(err.shift(0, self.decoded_len).into()),
+            Some(Slot::Io(err)) => Err(err)
+        };
+        if let Err(err) = ret {
+            self.err = Some(copy_err(&err));
+            return Err(err);
+        }
+        Ok(true)
+    }
+}
+
+/// Return a copy of `err`, which keeps the `SnappyError` it wraps, if any.
+fn copy_err(err: &io::Error) -> io::Error {
+    match err.get_ref().and_then(|e| e.downcast_ref::<SnappyError>()) {
+        Some(&e) => e.into(),
+        None => io::Error::new(err.kind(), err.to_string())
+    }
+}
+
+impl<R: Read> Read for ParallelFrameDecoder<R> {
+    fn read(&mut self, p: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let data = self.fill_buf()?;
+            let n = std::cmp::min(p.len(), data.len());
+            p[..n].copy_from_slice(&data[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for ParallelFrameDecoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.i == self.j {
+            if !self.next_chunk()? {
+                break
+            }
+        }
+        Ok(&self.decoded[self.i..self.j])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.i = std::cmp::min(self.i + amt, self.j);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::frame::pool::{join, Pool};
+use crate::frame::{compress_chunk, CHUNK_PREFIX_LEN, MAGIC_CHUNK, MAX_BLOCK_SIZE, MAX_ENCODED_LEN_OF_MAX_BLOCK_SIZE};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::mpsc::Receiver;
+
+/// The input and the output buffer of a chunk.
+type Buffers = (Vec<u8>, Vec<u8>);
+
+/// A writer that compresses data to the Snappy framing format on a pool of threads.
+///
+/// Data is cut into chunks like `FrameEncoder` does, and the chunks are
+/// compressed on the threads and written in order, so the output is the same
+/// as `FrameEncoder`'s. Up to two chunks per thread are in flight, which bounds
+/// the memory to about 280 KiB per thread.
+/// Call `flush` to emit the buffered data. The buffered data is also flushed
+/// when the encoder is dropped, but errors are ignored there.
+///
+/// # Examples:
+///
+/// ```rust
+/// use std::io::Write;
+/// use xsnappy::{FrameEncoder, ParallelFrameEncoder};
+///
+/// let src = b"hello world! ".repeat(100000);
+/// let mut enc = ParallelFrameEncoder::new(vec![], 4);
+/// enc.write_all(&src).unwrap();
+/// let framed = enc.into_inner().unwrap();
+///
+/// let mut enc = FrameEncoder::new(vec![]);
+/// enc.write_all(&src).unwrap();
+/// assert_eq!(framed, enc.into_inner().unwrap());
+/// ```
+pub struct ParallelFrameEncoder<W: Write> {
+    w: Option<W>,
+    err: Option<io::Error>,
+    pool: Pool,
+    /// The data of the next chunk.
+    buf: Vec<u8>,
+    /// The chunks being compressed, in stream order.
+    in_flight: VecDeque<Receiver<Buffers>>,
+    /// Buffers to reuse.
+    spare: Vec<Buffers>,
+    wrote_stream_header: bool,
+}
+
+impl<W: Write> ParallelFrameEncoder<W> {
+    /// Create an encoder that writes the compressed stream to `w`, compressing on
+    /// `threads` threads, or on as many as the available parallelism if `threads` is 0.
+    pub fn new(w: W, threads: usize) -> ParallelFrameEncoder<W> {
+        ParallelFrameEncoder {
+            w: Some(w),
+            err: None,
+            pool: Pool::new(threads),
+            buf: Vec::with_capacity(MAX_BLOCK_SIZE),
+            in_flight: VecDeque::new(),
+            spare: Vec::new(),
+            wrote_stream_header: false,
+        }
+    }
+
+    /// Flush the buffered data and return the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.w.take().unwrap())
+    }
+
+    /// Return a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.w.as_ref().unwrap()
+    }
+
+    /// Return a mutable reference to the underlying writer.
+    /// Writing to it directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.w.as_mut().unwrap()
+    }
+
+    /// Return the error of an earlier failed write, if any.
+    fn check_err(&self) -> io::Result<()> {
+        match &self.err {
+            Some(err) => Err(io::Error::new(err.kind(), err.to_string())),
+            None => Ok(())
+        }
+    }
+
+    /// Send the buffered data to be compressed, first writing out the oldest
+    /// chunk if too many are in flight.
+    fn send(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(())
+        }
+        if self.in_flight.len() >= 2 * self.pool.threads() {
+            self.write_oldest()?;
+        }
+        let (next, mut out) = self.spare.pop().unwrap_or_else(|| (Vec::with_capacity(MAX_BLOCK_SIZE), Vec::new()));
+        let src = std::mem::replace(&mut self.buf, next);
+        self.in_flight.push_back(self.pool.spawn(move || {
+            out.resize(CHUNK_PREFIX_LEN + MAX_ENCODED_LEN_OF_MAX_BLOCK_SIZE, 0);
+            let n = compress_chunk(&mut out, &src);
+            out.truncate(n);
+            (src, out)
+        }));
+        Ok(())
+    }
+
+    /// Wait for the oldest chunk in flight and write it out. A failure is kept
+    /// and returned by every later call.
+    fn write_oldest(&mut self) -> io::Result<()> {
+        self.check_err()?;
+        let rx = self.in_flight.pop_front().unwrap();
+        let (mut src, out) = join(&rx)?;
+        let w = self.w.as_mut().unwrap();
+        let mut ret = Ok(());
+        if !self.wrote_stream_header {
+            ret = w.write_all(MAGIC_CHUNK);
+            self.wrote_stream_header = true;
+        }
+        if let Err(err) = ret.and_then(|_| w.write_all(&out)) {
+            let ret = io::Error::new(err.kind(), err.to_string());
+            self.err = Some(err);
+            return Err(ret);
+        }
+        src.clear();
+        self.spare.push((src, out));
+        Ok(())
+    }
+
+    /// Compress the buffered data and write out every chunk in flight.
+    fn flush_buf(&mut self) -> io::Result<()> {
+        self.check_err()?;
+        self.send()?;
+        while !self.in_flight.is_empty() {
+            self.write_oldest()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ParallelFrameEncoder<W> {
+    fn write(&mut self, mut p: &[u8]) -> io::Result<usize> {
+        self.check_err()?;
+        let n = p.len();
+        while p.len() > MAX_BLOCK_SIZE - self.buf.len() {
+            if self.buf.is_empty() {
+                // Large write, empty buffer. Send every chunk of p, the last included, like `FrameEncoder`.
+                for chunk in p.chunks(MAX_BLOCK_SIZE) {
+                    self.buf.extend_from_slice(chunk);
+                    self.send()?;
+                }
+                return Ok(n)
+            }
+            let m = MAX_BLOCK_SIZE - self.buf.len();
+            self.buf.extend_from_slice(&p[..m]);
+            self.send()?;
+            p = &p[m..];
+        }
+        self.buf.extend_from_slice(p);
+        Ok(n)
+    }
+
+    /// Emit the buffered data as a chunk and flush the underlying writer.
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf()?;
+        self.w.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for ParallelFrameEncoder<W> {
+    fn drop(&mut self) {
+        if self.w.is_some() {
+            let _ = self.flush_buf();
+        }
+    }
+}
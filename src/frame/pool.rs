@@ -0,0 +1,67 @@
+//! The worker threads of the parallel encoder and decoder.
+
+use std::io;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A fixed set of threads running jobs in the order they are spawned.
+pub(crate) struct Pool {
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Pool {
+    /// Start `threads` threads, or as many as the available parallelism if `threads` is 0.
+    pub(crate) fn new(threads: usize) -> Pool {
+        let threads = match threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n
+        };
+        let (tx, rx) = channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        let workers = (0..threads).map(|_| {
+            let rx = Arc::clone(&rx);
+            std::thread::spawn(move || loop {
+                let job = rx.lock().unwrap().recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break
+                }
+            })
+        }).collect();
+        Pool { jobs: Some(tx), workers }
+    }
+
+    /// Return the number of threads.
+    pub(crate) fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Run `f` on a thread and return the receiver of its result.
+    pub(crate) fn spawn<T, F>(&self, f: F) -> Receiver<T>
+        where T: Send + 'static, F: FnOnce() -> T + Send + 'static {
+        let (tx, rx) = channel();
+        let job = Box::new(move || {
+            let _ = tx.send(f());
+        });
+        self.jobs.as_ref().unwrap().send(job).unwrap();
+        rx
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Wait for the result of a job.
+pub(crate) fn join<T>(rx: &Receiver<T>) -> io::Result<T> {
+    rx.recv().map_err(|_| io::Error::other("snappy: a worker thread panicked"))
+}
@@ -0,0 +1,275 @@
+//! The block stream of Hadoop's `SnappyCodec`, the `.snappy` files of Hadoop,
+//! Hive and Spark, which is neither a single block nor the framing format.
+//!
+//! A stream is a sequence of blocks. Every block is the big-endian 32-bit length
+//! of its uncompressed data, followed by chunks, each a big-endian 32-bit length
+//! and a Snappy block. The chunks of a block decode to its uncompressed length
+//! together. There are no checksums.
+
+use crate::encode::{max_encode_len, Encoder};
+use crate::decode::{decode, decode_len, DecodeOptions};
+use crate::error::CorruptKind;
+use crate::stream::{read_full, BlockDecoder, BlockEncoder, BlockReader, BlockWriter, Decoded};
+use std::io::{self, Read, BufRead, Write};
+
+/// Hadoop's default `io.compression.codec.snappy.buffersize`, 256 KiB.
+pub const DEFAULT_BUFFER_SIZE: usize = 256 * 1024;
+
+/// The smallest buffer size, which leaves room for some data in every block.
+pub const MIN_BUFFER_SIZE: usize = 64;
+
+/// The largest buffer size, as the lengths in a stream are 32-bit signed integers in Hadoop.
+pub const MAX_BUFFER_SIZE: usize = 1 << 30;
+
+/// The length of a block or chunk length.
+const LEN_SIZE: usize = 4;
+
+/// Return the max uncompressed length of a block written with `buffer_size`,
+/// which leaves room for the worst case of the compressed data in the buffer,
+/// like Hadoop's `BlockCompressorStream`.
+fn max_input_len(buffer_size: usize) -> usize {
+    buffer_size - (buffer_size / 6 + 32)
+}
+
+fn check_buffer_size(buffer_size: usize) {
+    assert!((MIN_BUFFER_SIZE..=MAX_BUFFER_SIZE).contains(&buffer_size),
+            "snappy: hadoop buffer size must be from {} to {}", MIN_BUFFER_SIZE, MAX_BUFFER_SIZE);
+}
+
+/// A writer that compresses data to the block stream of Hadoop's `SnappyCodec`.
+///
+/// Written data is buffered and emitted as a block of a single chunk whenever
+/// the buffer is full, like Hadoop does, so the blocks fit the buffers of a
+/// Hadoop reader with the same buffer size. Call `flush` to emit the buffered
+/// data. The buffered data is also flushed when the writer is dropped, but
+/// errors are ignored there.
+///
+/// # Examples:
+///
+/// ```rust
+/// use std::io::Write;
+/// use xsnappy::hadoop::HadoopWriter;
+///
+/// let mut w = HadoopWriter::new(vec![]);
+/// w.write_all(b"hello").unwrap();
+/// let stream = w.into_inner().unwrap();
+/// assert_eq!(stream, b"\x00\x00\x00\x05\x00\x00\x00\x07\x05\x10hello");
+/// ```
+pub struct HadoopWriter<W: Write> {
+    inner: BlockWriter<W, HadoopEncoder>,
+}
+
+/// Compresses a block to a block of a single chunk.
+struct HadoopEncoder {
+    enc: Encoder,
+    out: Vec<u8>,
+}
+
+impl BlockEncoder for HadoopEncoder {
+    fn encode(&mut self, src: &[u8]) -> io::Result<&[u8]> {
+        if src.is_empty() {
+            return Ok(&[])
+        }
+        let n = self.enc.compress(&mut self.out[2 * LEN_SIZE..], src)?;
+        self.out[..LEN_SIZE].copy_from_slice(&(src.len() as u32).to_be_bytes());
+        self.out[LEN_SIZE..2 * LEN_SIZE].copy_from_slice(&(n as u32).to_be_bytes());
+        Ok(&self.out[..2 * LEN_SIZE + n])
+    }
+}
+
+impl<W: Write> HadoopWriter<W> {
+    /// Create a writer with Hadoop's default buffer size, that writes the compressed stream to `w`.
+    pub fn new(w: W) -> HadoopWriter<W> {
+        HadoopWriter::with_buffer_size(w, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Create a writer for readers with a buffer of `buffer_size` bytes, as set by
+    /// `io.compression.codec.snappy.buffersize`.
+    /// # Panics:
+    /// Panics if `buffer_size` is not from `MIN_BUFFER_SIZE` to `MAX_BUFFER_SIZE`.
+    pub fn with_buffer_size(w: W, buffer_size: usize) -> HadoopWriter<W> {
+        check_buffer_size(buffer_size);
+        let max_input_len = max_input_len(buffer_size);
+        let enc = HadoopEncoder {
+            enc: Encoder::new(),
+            out: vec![0; 2 * LEN_SIZE + max_encode_len(max_input_len)],
+        };
+        HadoopWriter { inner: BlockWriter::new(w, max_input_len, enc) }
+    }
+
+    /// Flush the buffered data and return the underlying writer.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.inner.into_inner()
+    }
+
+    /// Return a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.get_ref()
+    }
+
+    /// Return a mutable reference to the underlying writer.
+    /// Writing to it directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.get_mut()
+    }
+}
+
+impl<W: Write> Write for HadoopWriter<W> {
+    fn write(&mut self, p: &[u8]) -> io::Result<usize> {
+        self.inner.write(p)
+    }
+
+    /// Emit the buffered data as a block and flush the underlying writer.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader that decompresses the block stream of Hadoop's `SnappyCodec`.
+///
+/// Like Hadoop's decompressor, it rejects chunks that are longer than the
+/// buffer size, compressed or not, with `CorruptKind::ChunkLength`, which
+/// bounds the memory it takes. Streams written with a larger buffer size need
+/// a reader with the same. A chunk that decodes past the length of its block
+/// is rejected the same way, and a stream that ends inside a block with
+/// `CorruptKind::TruncatedChunk`.
+/// An error in the stream is returned by every later read.
+///
+/// # Examples:
+///
+/// ```rust
+/// use std::io::{Read, Write};
+/// use xsnappy::hadoop::{HadoopReader, HadoopWriter};
+///
+/// let mut w = HadoopWriter::new(vec![]);
+/// w.write_all(b"hello world! hello world!").unwrap();
+/// let stream = w.into_inner().unwrap();
+///
+/// let mut dst = vec![];
+/// HadoopReader::new(&stream[..]).read_to_end(&mut dst).unwrap();
+/// assert_eq!(dst, b"hello world! hello world!");
+/// ```
+pub struct HadoopReader<R: Read> {
+    inner: BlockReader<R, HadoopDecoder>,
+}
+
+/// Reads the chunks of the blocks of a stream.
+struct HadoopDecoder {
+    buffer_size: usize,
+    options: DecodeOptions,
+    body: Vec<u8>,
+    /// The uncompressed length of the current block not decoded yet.
+    block_left: usize,
+}
+
+impl<R: Read> HadoopReader<R> {
+    /// Create a reader with Hadoop's default buffer size, that reads the compressed stream from `r`.
+    pub fn new(r: R) -> HadoopReader<R> {
+        HadoopReader::with_buffer_size(r, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Create a reader for streams written with a buffer of `buffer_size` bytes, as set by
+    /// `io.compression.codec.snappy.buffersize`.
+    /// # Panics:
+    /// Panics if `buffer_size` is not from `MIN_BUFFER_SIZE` to `MAX_BUFFER_SIZE`.
+    pub fn with_buffer_size(r: R, buffer_size: usize) -> HadoopReader<R> {
+        HadoopReader::with_options(r, buffer_size, DecodeOptions::default())
+    }
+
+    /// Create a reader like `with_buffer_size` that fails with `SnappyError::LimitExceeded` once
+    /// the stream decodes to more than `max_decoded_len` bytes in total, or a chunk exceeds `max_ratio`.
+    /// # Panics:
+    /// Panics if `buffer_size` is not from `MIN_BUFFER_SIZE` to `MAX_BUFFER_SIZE`.
+    pub fn with_options(r: R, buffer_size: usize, options: DecodeOptions) -> HadoopReader<R> {
+        check_buffer_size(buffer_size);
+        let dec = HadoopDecoder { buffer_size, options, body: Vec::new(), block_left: 0 };
+        HadoopReader { inner: BlockReader::new(r, dec) }
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner.r
+    }
+
+    /// Return a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner.r
+    }
+
+    /// Return a mutable reference to the underlying reader.
+    /// Reading from it directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner.r
+    }
+}
+
+/// Read a length at the current input offset. Return `None` at the end of the
+/// stream, if it may end there.
+fn read_len<R: Read>(r: &mut R, d: &mut Decoded, may_end: bool) -> io::Result<Option<usize>> {
+    let mut len = [0; LEN_SIZE];
+    match read_full(r, &mut len)? {
+        0 if may_end => return Ok(None),
+        LEN_SIZE => {}
+        _ => return Err(d.fail(d.corrupt(CorruptKind::TruncatedChunk, d.pos)))
+    }
+    d.pos += LEN_SIZE;
+    Ok(Some(u32::from_be_bytes(len) as usize))
+}
+
+impl<R: Read> BlockDecoder<R> for HadoopDecoder {
+    /// Read and decode the next chunk, reading the length of its block first
+    /// if the current block is done. Return false at the end of the stream.
+    fn next_block(&mut self, r: &mut R, d: &mut Decoded) -> io::Result<bool> {
+        while self.block_left == 0 {
+            match read_len(r, d, true)? {
+                Some(len) => self.block_left = len,
+                None => return Ok(false)
+            }
+        }
+        let pos = d.pos;
+        let len = read_len(r, d, false)?.unwrap();
+        if len > self.buffer_size {
+            return Err(d.fail(d.corrupt(CorruptKind::ChunkLength, pos)))
+        }
+        self.body.resize(len, 0);
+        if read_full(r, &mut self.body)? < len {
+            return Err(d.fail(d.corrupt(CorruptKind::TruncatedChunk, pos)))
+        }
+        d.pos += len;
+        let n = match decode_len(&self.body) {
+            Ok(n) => n,
+            Err(err) => return Err(d.fail(err.shift(pos + LEN_SIZE, d.decoded_len)))
+        };
+        if n > self.buffer_size || n > self.block_left {
+            return Err(d.fail(d.corrupt(CorruptKind::ChunkLength, pos)))
+        }
+        let limits = self.options.check_len(d.decoded_len.saturating_add(n))
+            .and_then(|_| self.options.check_ratio(n, self.body.len()));
+        if let Err(err) = limits {
+            return Err(d.fail(err))
+        }
+        d.buf.resize(n, 0);
+        if let Err(err) = decode(&mut d.buf, &self.body) {
+            return Err(d.fail(err.shift(pos + LEN_SIZE, d.decoded_len)))
+        }
+        self.block_left -= n;
+        d.decoded(n);
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for HadoopReader<R> {
+    fn read(&mut self, p: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(p)
+    }
+}
+
+impl<R: Read> BufRead for HadoopReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
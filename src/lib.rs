@@ -5,8 +5,54 @@ It is ported from [Go implementation](https://github.com/golang/snappy) with sim
 Provide basic interfaces:
 - `max_encode_len(src_len)`: Get the max length of encoded data.
 - `encode(dst, src)`: Encode `src` to `dst`.
+- `try_encode(dst, src)`: Encode `src` to `dst`, returning an error instead of panicking.
+- `encode_with_scratch(dst, src, scratch)`: Encode `src` to `dst` with a caller-provided `EncodeScratch` hash table.
+- `encode_with_options(dst, src, options)`: Encode `src` to `dst` at a `CompressionLevel`.
+- `encode_parallel(src, threads)`: Encode `src` on several threads, to the same bytes as `encode`.
 - `decode_len(src)`: Get the exact length of decoded data.
-- `decode_len(dst, src)`: Decode `src` to `dst`.
+- `decode(dst, src)`: Decode `src` to `dst`.
+- `validate(src)`: Check `src` without decoding it.
+- `ops(src)`: Iterate over the literals and copies of `src` without decoding it.
+
+Errors are `SnappyError`s, which implement `std::error::Error` and convert to `io::Error`.
+A corrupt input error carries the input and output offsets and the tag where decoding failed.
+
+The `_with_options` variants of the decoding functions, `Decoder` and `FrameDecoder`
+take `DecodeOptions` to reject decoded data beyond a length or a ratio.
+
+`Encoder` is a reusable encoder that keeps its hash table on the heap,
+and `Decoder` is a reusable decoder that keeps its output buffer.
+
+And allocating helpers built on them:
+- `compress_to_vec(src)`, `decompress_to_vec(src)`: Return the encoded or decoded data in a new `Vec`.
+- `compress_into(dst, src)`, `decompress_into(dst, src)`: Append the encoded or decoded data to `dst`.
+
+Also provide `FrameEncoder` and `FrameDecoder` for the [framing format](https://github.com/google/snappy/blob/master/framing_format.txt),
+and the CRC-32C checksum it uses in the `crc32c` module.
+`ParallelFrameEncoder` compresses the chunks on a pool of threads, to the same bytes as `FrameEncoder`,
+and `ParallelFrameDecoder` reads ahead and decompresses them on a pool of threads.
+`chunks(src)` iterates over the chunks of a framed stream in memory, checking them the way `FrameDecoder` does.
+With the `tokio` or `futures-io` feature, `AsyncFrameEncoder` and `AsyncFrameDecoder`
+do the same over async IO, producing the same bytes as the blocking ones.
+With the `tokio-util` feature, `SnappyCodec` frames messages as a varint length
+and a Snappy block each.
+The `hadoop` module reads and writes the block stream of Hadoop's `SnappyCodec`,
+and the `xerial` module the stream format of snappy-java, used by Kafka.
+
+The `s2` module encodes and decodes the block format of S2, which extends Snappy with repeats.
+
+`builder::StreamBuilder` writes blocks operation by operation, to hand-craft test vectors.
+
+# Features:
+
+- `std` (default): everything that needs the standard library: the framing format,
+  the `hadoop` and `xerial` modules, the parallel encoders and decoders, and the `std::error::Error` and `io::Error` impls of `SnappyError`.
+- `alloc` (implied by `std`): the `Vec` helpers, `encode_with_options`, `Encoder`, `Decoder` and `builder`.
+- `tokio`, `futures-io`: `AsyncFrameEncoder` and `AsyncFrameDecoder`.
+- `tokio-util`: `SnappyCodec`.
+
+With `default-features = false`, the crate is `no_std` and keeps the block functions
+`encode`, `try_encode`, `encode_with_scratch`, `decode`, `validate` and `ops`, and the `crc32c` and `s2` modules.
 
 # Examples:
 
@@ -45,11 +91,51 @@ println!("{}", from_utf8(&dst).unwrap());
 
 */
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 mod binary;
 mod encode;
+mod encode_better;
 mod decode;
+#[cfg(feature = "std")]
+mod frame;
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "alloc")]
+mod vec;
+mod ops;
+#[cfg(feature = "tokio-util")]
+mod codec;
 pub mod error;
+pub mod crc32c;
+pub mod s2;
+#[cfg(feature = "alloc")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod hadoop;
+#[cfg(feature = "std")]
+pub mod xerial;
 use error::SnappyError;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+pub use frame::{FrameEncoder, FrameDecoder, ParallelFrameEncoder, ParallelFrameDecoder};
+#[cfg(feature = "std")]
+pub use frame::{Chunks, Chunk, ChunkType};
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub use frame::{AsyncFrameEncoder, AsyncFrameDecoder};
+pub use encode::{EncodeOptions, CompressionLevel, EncodeScratch};
+#[cfg(feature = "alloc")]
+pub use encode::Encoder;
+pub use decode::DecodeOptions;
+#[cfg(feature = "alloc")]
+pub use decode::Decoder;
+pub use ops::{Ops, Op};
+#[cfg(feature = "tokio-util")]
+pub use codec::SnappyCodec;
 
 
 /// Encode `src` to `dst`. The `dst` must be initialized with a certain length.
@@ -70,21 +156,182 @@ pub fn encode(dst: &mut [u8], src: &[u8]) -> usize {
     encode::encode(dst, src)
 }
 
+/// Encode `src` to `dst`. Unlike `encode`, it never panics and `dst` may be
+/// shorter than `max_encode_len(src.len())`.
+/// Return the exact length of encoded data, `SnappyError::EncodeTooLarge` if `src`
+/// is too large to be encoded, or `SnappyError::DstTooSmall` if the encoded data
+/// doesn't fit in `dst`.
+/// # Examples:
+///
+/// ```rust
+/// use xsnappy::try_encode;
+/// use xsnappy::error::SnappyError;
+///
+/// // Compress only if it fits in a 4 KiB page.
+/// let mut page = [0; 4096];
+/// let len = try_encode(&mut page, &[b'a'; 50000]).unwrap();
+/// assert!(len < 4096);
+///
+/// let mut x = 1u32;
+/// let noise: Vec<u8> = (0..5000).map(|_| { x ^= x << 13; x ^= x >> 17; x ^= x << 5; x as u8 }).collect();
+/// match try_encode(&mut page, &noise) {
+///     Err(SnappyError::DstTooSmall) => {}
+///     _ => unreachable!()
+/// }
+/// ```
+pub fn try_encode(dst: &mut [u8], src: &[u8]) -> Result<usize, SnappyError> {
+    encode::try_encode(dst, src)
+}
+
+/// Encode `src` to `dst` like `try_encode`, but with the hash table of `scratch`
+/// instead of one on the stack. With the default table size the output is the
+/// same as `encode`'s, and a smaller `EncodeScratch<N>` compresses a little worse.
+/// # Examples:
+///
+/// ```rust
+/// use xsnappy::{encode_with_scratch, max_encode_len, EncodeScratch};
+///
+/// // Keep the table out of the stack frame, e.g. in a struct that outlives the calls.
+/// let mut scratch: Box<EncodeScratch> = Box::new(EncodeScratch::new());
+///
+/// let src = b"hello world! hello world!";
+/// let mut dst = [0; 64];
+/// let len = encode_with_scratch(&mut dst, src, &mut scratch).unwrap();
+/// assert!(len <= max_encode_len(src.len()));
+///
+/// // A 4 KiB table.
+/// let mut small = EncodeScratch::<2048>::new();
+/// let len = encode_with_scratch(&mut dst, src, &mut small).unwrap();
+/// assert!(len <= max_encode_len(src.len()));
+/// ```
+pub fn encode_with_scratch<const N: usize>(dst: &mut [u8], src: &[u8], scratch: &mut EncodeScratch<N>) -> Result<usize, SnappyError> {
+    encode::encode_with_scratch(dst, src, scratch)
+}
+
+/// Encode `src` to `dst` at the level of `options`, with the same errors as `try_encode`.
+/// Every level produces standard Snappy data that any decoder reads.
+/// # Examples:
+///
+/// ```rust
+/// use xsnappy::{encode_with_options, max_encode_len, EncodeOptions, CompressionLevel};
+///
+/// let src = b"hello world! hello world! hello world!";
+/// let mut dst = vec![0; max_encode_len(src.len())];
+/// let options = EncodeOptions { level: CompressionLevel::Better };
+/// let len = encode_with_options(&mut dst, src, &options).unwrap();
+/// dst.resize(len, 0);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_with_options(dst: &mut [u8], src: &[u8], options: &EncodeOptions) -> Result<usize, SnappyError> {
+    encode::encode_with_options(dst, src, options)
+}
+
+/// Encode `src` on `threads` threads and return the encoded data, which is the same
+/// as `encode` produces. A `threads` of 0 uses the available parallelism.
+/// Return `SnappyError::EncodeTooLarge` if `src` is too large to be encoded.
+/// # Examples:
+///
+/// ```rust
+/// use xsnappy::{encode_parallel, compress_to_vec};
+///
+/// let src = b"hello world! ".repeat(100000);
+/// let compressed = encode_parallel(&src, 4).unwrap();
+/// assert_eq!(compressed, compress_to_vec(&src).unwrap());
+/// ```
+#[cfg(feature = "std")]
+pub fn encode_parallel(src: &[u8], threads: usize) -> Result<Vec<u8>, SnappyError> {
+    encode::encode_parallel(src, threads)
+}
+
 /// Decode `src` to `dst`. The `dst` must be initialized with a certain length.
-/// Return the exact length of decoded data.
+/// Return the exact length of decoded data. A `SnappyError::Corrupt` error tells
+/// where and why decoding failed.
 /// # Examples:
 ///
 /// ```rust
-/// use xsnappy::{decode_len, decode};
+/// # #[cfg(feature = "alloc")] {
+/// use xsnappy::{decode_len, decode, compress_to_vec};
 ///
-/// let dec_len = decode_len(src).unwrap();
+/// let src = compress_to_vec(b"hello world! hello world!").unwrap();
+/// let dec_len = decode_len(&src).unwrap();
 /// let mut dst = vec![0; dec_len];
-/// decode(&mut dst, src);
+/// decode(&mut dst, &src).unwrap();
+///
+/// let err = decode(&mut dst, &src[..src.len()-1]).unwrap_err();
+/// println!("{}", err); // snappy: corrupt input: ... at input byte ...
+/// # }
 /// ```
 pub fn decode(dst: &mut [u8], src: &[u8]) -> Result<usize, SnappyError> {
     decode::decode(dst, src)
 }
 
+/// Decode `src` to `dst` like `decode`, if it is within the limits of `options`.
+/// Return `SnappyError::LimitExceeded` otherwise.
+pub fn decode_with_options(dst: &mut [u8], src: &[u8], options: &DecodeOptions) -> Result<usize, SnappyError> {
+    decode::decode_with_options(dst, src, options)
+}
+
+/// Check that `src` is valid encoded data without decoding it, which is much cheaper
+/// than `decode` and needs no `dst`. Return the exact length of decoded data.
+/// # Examples:
+///
+/// ```rust
+/// # #[cfg(feature = "alloc")] {
+/// use xsnappy::{validate, compress_to_vec};
+///
+/// let compressed = compress_to_vec(b"hello world! hello world!").unwrap();
+/// assert_eq!(validate(&compressed).unwrap(), 25);
+/// assert!(validate(&compressed[..compressed.len()-1]).is_err());
+/// # }
+/// ```
+pub fn validate(src: &[u8]) -> Result<usize, SnappyError> {
+    decode::validate(src)
+}
+
+/// Return an iterator over the literals and copies of `src`, each with the position
+/// in the decoded data it writes to. Nothing is decoded, which makes it useful
+/// to inspect the compression of data or to find where corrupt data goes wrong.
+/// # Examples:
+///
+/// ```rust
+/// # #[cfg(feature = "alloc")] {
+/// use xsnappy::{ops, compress_to_vec, Op};
+///
+/// let compressed = compress_to_vec(b"hello world! hello world!").unwrap();
+/// for op in ops(&compressed).unwrap() {
+///     match op.unwrap() {
+///         (pos, Op::Literal { len, input_range }) => println!("{}: literal {} at {:?}", pos, len, input_range),
+///         (pos, Op::Copy { offset, len, tag }) => println!("{}: {:?} {} from {}", pos, tag, len, offset),
+///     }
+/// }
+/// # }
+/// ```
+pub fn ops(src: &[u8]) -> Result<Ops<'_>, SnappyError> {
+    Ops::new(src)
+}
+
+/// Return an iterator over the chunks of the framed stream `src`. Every chunk is
+/// checked the way `FrameDecoder` checks it, so the iterator fails on the same streams.
+/// `Chunks::ignore_checksums` lists the chunks past checksum mismatches.
+/// # Examples:
+///
+/// ```rust
+/// use std::io::Write;
+/// use xsnappy::{chunks, FrameEncoder};
+///
+/// let mut enc = FrameEncoder::new(vec![]);
+/// enc.write_all(b"hello world! hello world!").unwrap();
+/// let framed = enc.into_inner().unwrap();
+/// for chunk in chunks(&framed) {
+///     let chunk = chunk.unwrap();
+///     println!("{}: {:?} of {} bytes, {} decoded", chunk.offset, chunk.chunk_type, chunk.body.len(), chunk.decoded_len);
+/// }
+/// ```
+#[cfg(feature = "std")]
+pub fn chunks(src: &[u8]) -> Chunks<'_> {
+    Chunks::new(src)
+}
+
 /// Return the max length of encoded data
 pub fn max_encode_len(src_len: usize) -> usize {
     encode::max_encode_len(src_len)
@@ -93,4 +340,85 @@ pub fn max_encode_len(src_len: usize) -> usize {
 /// Return the exact length of decoded data.
 pub fn decode_len(src: &[u8]) -> Result<usize, SnappyError> {
     decode::decode_len(src)
-}
\ No newline at end of file
+}
+
+/// Encode `src` and return the encoded data.
+/// # Examples:
+///
+/// ```rust
+/// use xsnappy::{compress_to_vec, decompress_to_vec};
+///
+/// let src = b"hello world! hello world!";
+/// let compressed = compress_to_vec(src).unwrap();
+/// assert_eq!(decompress_to_vec(&compressed).unwrap(), src);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn compress_to_vec(src: &[u8]) -> Result<Vec<u8>, SnappyError> {
+    let mut dst = Vec::new();
+    vec::compress_into(&mut dst, src)?;
+    Ok(dst)
+}
+
+/// Decode `src` and return the decoded data.
+#[cfg(feature = "alloc")]
+pub fn decompress_to_vec(src: &[u8]) -> Result<Vec<u8>, SnappyError> {
+    decompress_to_vec_with_options(src, &DecodeOptions::default())
+}
+
+/// Decode `src` and return the decoded data, if it is within the limits of `options`.
+/// The limits are checked before anything is allocated.
+/// # Examples:
+///
+/// ```rust
+/// use xsnappy::{decompress_to_vec_with_options, DecodeOptions};
+/// use xsnappy::error::SnappyError;
+///
+/// // The header claims 4 GiB of decoded data.
+/// let bomb = [0xff, 0xff, 0xff, 0xff, 0x0f, 0x00];
+/// let options = DecodeOptions { max_decoded_len: Some(1 << 20), max_ratio: None };
+/// match decompress_to_vec_with_options(&bomb, &options) {
+///     Err(SnappyError::LimitExceeded) => {}
+///     _ => unreachable!()
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+pub fn decompress_to_vec_with_options(src: &[u8], options: &DecodeOptions) -> Result<Vec<u8>, SnappyError> {
+    let mut dst = Vec::new();
+    vec::decompress_into(&mut dst, src, options)?;
+    Ok(dst)
+}
+
+/// Encode `src` and append the encoded data to `dst`.
+/// Return the length of the appended data.
+/// # Examples:
+///
+/// ```rust
+/// use xsnappy::{compress_into, decompress_into};
+///
+/// let mut compressed = b"header".to_vec();
+/// let len = compress_into(&mut compressed, b"hello world! hello world!").unwrap();
+/// assert_eq!(compressed.len(), 6 + len);
+///
+/// let mut dst = vec![];
+/// decompress_into(&mut dst, &compressed[6..]).unwrap();
+/// assert_eq!(dst, b"hello world! hello world!");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn compress_into(dst: &mut Vec<u8>, src: &[u8]) -> Result<usize, SnappyError> {
+    vec::compress_into(dst, src)
+}
+
+/// Decode `src` and append the decoded data to `dst`.
+/// Return the length of the appended data.
+/// `dst` is unchanged on error.
+#[cfg(feature = "alloc")]
+pub fn decompress_into(dst: &mut Vec<u8>, src: &[u8]) -> Result<usize, SnappyError> {
+    vec::decompress_into(dst, src, &DecodeOptions::default())
+}
+
+/// Decode `src` and append the decoded data to `dst`, like `decompress_into`,
+/// if it is within the limits of `options`.
+#[cfg(feature = "alloc")]
+pub fn decompress_into_with_options(dst: &mut Vec<u8>, src: &[u8], options: &DecodeOptions) -> Result<usize, SnappyError> {
+    vec::decompress_into(dst, src, options)
+}
@@ -0,0 +1,100 @@
+use crate::decode::{_decode_len, read_tag, check_copy, Tag};
+use crate::error::{SnappyError, CorruptKind, TagKind};
+use core::ops::Range;
+
+/// An operation of the encoded data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// A literal of `len` bytes, found at `input_range` of the encoded data.
+    Literal { len: usize, input_range: Range<usize> },
+    /// A copy of `len` bytes from `offset` bytes back, encoded with a tag of `tag`.
+    Copy { offset: usize, len: usize, tag: TagKind },
+}
+
+/// An iterator over the operations of encoded data, created by `ops`.
+///
+/// It yields every operation with the position in the decoded data it writes to,
+/// and checks it the way `validate` does. Decoding stops at the first error.
+pub struct Ops<'a> {
+    src: &'a [u8],
+    s: usize,
+    d: usize,
+    d_len: usize,
+    done: bool,
+}
+
+impl<'a> Ops<'a> {
+    pub(crate) fn new(src: &'a [u8]) -> Result<Ops<'a>, SnappyError> {
+        let (d_len, s) = _decode_len(src)?;
+        Ok(Ops { src, s, d: 0, d_len, done: false })
+    }
+
+    /// Return the exact length of decoded data, as the header claims.
+    pub fn decoded_len(&self) -> usize {
+        self.d_len
+    }
+
+    /// Return the position in the encoded data of the next operation.
+    pub fn input_offset(&self) -> usize {
+        self.s
+    }
+
+    fn next_op(&mut self) -> Result<Option<(usize, Op)>, SnappyError> {
+        let (s, d) = (self.s, self.d);
+        if s >= self.src.len() {
+            if d != self.d_len {
+                return Err(SnappyError::corrupt(CorruptKind::ShortOutput, s, d, None))
+            }
+            return Ok(None)
+        }
+        let (tag, next) = read_tag(self.src, s, d)?;
+        let op = match tag {
+            Tag::Literal { len } => {
+                if len > self.d_len - d {
+                    return Err(SnappyError::corrupt(CorruptKind::PastDecodedLen(len), s, d, Some(TagKind::Literal)))
+                }
+                self.s = next + len;
+                Op::Literal { len, input_range: next..next + len }
+            }
+            Tag::Copy { kind, offset, len } => {
+                check_copy(kind, offset, len, s, d, self.d_len - d)?;
+                self.s = next;
+                Op::Copy { offset, len, tag: kind }
+            }
+        };
+        self.d += op.output_len();
+        Ok(Some((d, op)))
+    }
+}
+
+impl Op {
+    /// Return the number of bytes the operation writes to the decoded data.
+    pub fn output_len(&self) -> usize {
+        match *self {
+            Op::Literal { len, .. } | Op::Copy { len, .. } => len
+        }
+    }
+}
+
+impl<'a> Iterator for Ops<'a> {
+    type Item = Result<(usize, Op), SnappyError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None
+        }
+        match self.next_op() {
+            Ok(Some(op)) => Some(Ok(op)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl<'a> core::iter::FusedIterator for Ops<'a> {}
@@ -0,0 +1,291 @@
+use crate::binary::{load32, load64};
+use crate::encode::emit_literal;
+use crate::error::{SnappyError, TAG_COPY1, TAG_COPY2, TAG_COPY4};
+
+/// The bytes at the end of the input that the matcher leaves to the final literal.
+const INPUT_MARGIN: usize = 8;
+/// The shortest input `encode_block` accepts. Shorter inputs are emitted as a literal.
+pub(crate) const MIN_NON_LITERAL_BLOCK_SIZE: usize = 32;
+
+const TABLE_BITS: u32 = 14;
+/// The entry count of the hash table.
+pub(crate) const TABLE_SIZE: usize = 1 << TABLE_BITS;
+
+/// The hash table of `encode_with_scratch`, for callers that can't afford the
+/// 64 KiB table `encode` puts on the stack, nor an allocation per block.
+/// It can be a `static`, a field of a longer-lived struct, or boxed.
+pub struct EncodeScratch {
+    pub(crate) table: [u32; TABLE_SIZE],
+}
+
+impl EncodeScratch {
+    /// Create a scratch table.
+    pub const fn new() -> EncodeScratch {
+        EncodeScratch { table: [0; TABLE_SIZE] }
+    }
+}
+
+impl Default for EncodeScratch {
+    fn default() -> EncodeScratch {
+        EncodeScratch::new()
+    }
+}
+
+/// The longest length of a single repeat.
+const MAX_REPEAT: usize = (1 << 24) - 1;
+
+const PRIME_6_BYTES: u64 = 227718039650203;
+
+/// Hash the low 6 bytes of `u` to `TABLE_BITS` bits.
+fn hash6(u: u64) -> usize {
+    ((u << 16).wrapping_mul(PRIME_6_BYTES) >> (64 - TABLE_BITS)) as usize
+}
+
+/// Emit a repeat of the previous offset of `length` bytes, at least 4, to `dst`.
+/// `offset` is the previous offset, which short repeats encode as a copy1 when it fits.
+fn emit_repeat(dst: &mut [u8], offset: usize, length: usize) -> usize {
+    let mut length = length - 4;
+    if length <= 4 {
+        dst[0] = (length as u8) << 2 | TAG_COPY1;
+        dst[1] = 0;
+        return 2
+    }
+    if length < 8 && offset < 2048 {
+        // A copy1 is as short, and keeps the offset.
+        dst[1] = offset as u8;
+        dst[0] = ((offset >> 8) as u8) << 5 | (length as u8) << 2 | TAG_COPY1;
+        return 2
+    }
+    if length < (1 << 8) + 4 {
+        length -= 4;
+        dst[2] = length as u8;
+        dst[1] = 0;
+        dst[0] = 5 << 2 | TAG_COPY1;
+        return 3
+    }
+    if length < (1 << 16) + (1 << 8) {
+        length -= 1 << 8;
+        dst[3] = (length >> 8) as u8;
+        dst[2] = length as u8;
+        dst[1] = 0;
+        dst[0] = 6 << 2 | TAG_COPY1;
+        return 4
+    }
+    length -= 1 << 16;
+    let mut left = 0;
+    if length > MAX_REPEAT {
+        left = length - MAX_REPEAT + 4;
+        length = MAX_REPEAT - 4;
+    }
+    dst[4] = (length >> 16) as u8;
+    dst[3] = (length >> 8) as u8;
+    dst[2] = length as u8;
+    dst[1] = 0;
+    dst[0] = 7 << 2 | TAG_COPY1;
+    if left > 0 {
+        return 5 + emit_repeat(&mut dst[5..], offset, left)
+    }
+    5
+}
+
+/// Emit a copy of `length` bytes, at least 4, from `offset` bytes back to `dst`.
+/// Long copies are emitted as a copy followed by repeats.
+fn emit_copy(dst: &mut [u8], offset: usize, mut length: usize) -> usize {
+    if offset >= 1 << 16 {
+        let mut i = 0;
+        if length > 64 {
+            dst[0] = 63 << 2 | TAG_COPY4;
+            dst[1..5].copy_from_slice(&(offset as u32).to_le_bytes());
+            length -= 64;
+            if length >= 4 {
+                return 5 + emit_repeat(&mut dst[5..], offset, length)
+            }
+            i = 5;
+        }
+        if length == 0 {
+            return i
+        }
+        dst[i] = ((length - 1) as u8) << 2 | TAG_COPY4;
+        dst[i + 1..i + 5].copy_from_slice(&(offset as u32).to_le_bytes());
+        return i + 5
+    }
+
+    if length > 64 {
+        let i;
+        if offset < 2048 {
+            // A copy1 of 8 bytes, then repeats.
+            dst[1] = offset as u8;
+            dst[0] = ((offset >> 8) as u8) << 5 | 4 << 2 | TAG_COPY1;
+            length -= 8;
+            i = 2;
+        } else {
+            // A copy2 of 60 bytes, then repeats.
+            dst[2] = (offset >> 8) as u8;
+            dst[1] = offset as u8;
+            dst[0] = 59 << 2 | TAG_COPY2;
+            length -= 60;
+            i = 3;
+        }
+        return i + emit_repeat(&mut dst[i..], offset, length)
+    }
+    if length >= 12 || offset >= 2048 {
+        dst[2] = (offset >> 8) as u8;
+        dst[1] = offset as u8;
+        dst[0] = ((length - 1) as u8) << 2 | TAG_COPY2;
+        return 3
+    }
+    dst[1] = offset as u8;
+    dst[0] = ((offset >> 8) as u8) << 5 | ((length - 4) as u8) << 2 | TAG_COPY1;
+    2
+}
+
+/// Return the end of the match between `src[s..]` and `src[i..]`, where `i < s`,
+/// stopping `INPUT_MARGIN` bytes before the end of `src`. Unlike the `extend_match`
+/// of the Snappy encoder it leaves the tail to the final literal, as the Go encoder does.
+fn extend_match(src: &[u8], mut s: usize, mut i: usize) -> usize {
+    while s + INPUT_MARGIN <= src.len() {
+        let diff = load64(src, s) ^ load64(src, i);
+        if diff != 0 {
+            return s + diff.trailing_zeros() as usize / 8
+        }
+        s += 8;
+        i += 8;
+    }
+    s
+}
+
+/// Encode `src`, which must be at least `MIN_NON_LITERAL_BLOCK_SIZE` long, to `dst`
+/// without the length header, using `table` of `TABLE_SIZE` entries as the hash table.
+/// `dst` must be at least `src.len() + literal_extra_size(src.len())` long.
+/// Return 0 if the encoded data isn't smaller than `src` by about 3%, in which case
+/// `src` should be emitted as a literal. Fails only if `dst` is too short.
+///
+/// Besides the matches of the hash table, it looks for a match at the offset of the
+/// previous copy one byte ahead, and emits it as a repeat.
+pub(crate) fn encode_block(dst: &mut [u8], src: &[u8], table: &mut [u32]) -> Result<usize, SnappyError> {
+    table.fill(0);
+    let s_limit = src.len() - INPUT_MARGIN;
+    // Bail if the encoded data grows beyond this.
+    let d_limit = src.len() - (src.len() >> 5) - 5;
+
+    let mut next_emit = 0;
+    let mut d = 0;
+    let mut s = 1;
+    let mut cv = load64(src, s);
+    // Look for a repeat at offset 1 from the start, but emit the first match as a copy.
+    let mut repeat = 1;
+
+    loop {
+        let mut candidate;
+        loop {
+            // Probe less often the longer we go without a match.
+            let next_s = s + ((s - next_emit) >> 6) + 4;
+            if next_s > s_limit {
+                return emit_remainder(dst, src, next_emit, d, d_limit)
+            }
+            let hash0 = hash6(cv);
+            let hash1 = hash6(cv >> 8);
+            candidate = table[hash0] as usize;
+            let candidate2 = table[hash1] as usize;
+            table[hash0] = s as u32;
+            table[hash1] = (s + 1) as u32;
+            let hash2 = hash6(cv >> 16);
+
+            // A repeat one byte ahead.
+            if (cv >> 8) as u32 == load32(src, s - repeat + 1) {
+                let mut base = s + 1;
+                let mut i = base - repeat;
+                while base > next_emit && i > 0 && src[i - 1] == src[base - 1] {
+                    i -= 1;
+                    base -= 1;
+                }
+                if d + (base - next_emit) > d_limit {
+                    return Ok(0)
+                }
+                d += emit_literal(&mut dst[d..], &src[next_emit..base])?;
+
+                s = extend_match(src, s + 5, s - repeat + 5);
+                if next_emit > 0 {
+                    d += emit_repeat(&mut dst[d..], repeat, s - base);
+                } else {
+                    // The first match can't be a repeat.
+                    d += emit_copy(&mut dst[d..], repeat, s - base);
+                }
+                next_emit = s;
+                if s >= s_limit {
+                    return emit_remainder(dst, src, next_emit, d, d_limit)
+                }
+                cv = load64(src, s);
+                continue
+            }
+
+            if cv as u32 == load32(src, candidate) {
+                break
+            }
+            candidate = table[hash2] as usize;
+            if (cv >> 8) as u32 == load32(src, candidate2) {
+                table[hash2] = (s + 2) as u32;
+                candidate = candidate2;
+                s += 1;
+                break
+            }
+            table[hash2] = (s + 2) as u32;
+            if (cv >> 16) as u32 == load32(src, candidate) {
+                s += 2;
+                break
+            }
+
+            cv = load64(src, next_s);
+            s = next_s;
+        }
+
+        // Extend the match backwards.
+        while candidate > 0 && s > next_emit && src[candidate - 1] == src[s - 1] {
+            candidate -= 1;
+            s -= 1;
+        }
+        if d + (s - next_emit) > d_limit {
+            return Ok(0)
+        }
+        d += emit_literal(&mut dst[d..], &src[next_emit..s])?;
+
+        // Emit copies as long as there is a match right after the previous one.
+        loop {
+            let base = s;
+            repeat = base - candidate;
+            s = extend_match(src, s + 4, candidate + 4);
+            d += emit_copy(&mut dst[d..], repeat, s - base);
+            next_emit = s;
+            if s >= s_limit {
+                return emit_remainder(dst, src, next_emit, d, d_limit)
+            }
+            if d > d_limit {
+                return Ok(0)
+            }
+
+            let x = load64(src, s - 2);
+            let prev_hash = hash6(x);
+            let curr_hash = hash6(x >> 16);
+            candidate = table[curr_hash] as usize;
+            table[prev_hash] = (s - 2) as u32;
+            table[curr_hash] = s as u32;
+            if (x >> 16) as u32 != load32(src, candidate) {
+                cv = load64(src, s + 1);
+                s += 1;
+                break
+            }
+        }
+    }
+}
+
+/// Emit the rest of `src` from `next_emit` on as a literal, unless it would
+/// exceed `d_limit`. Return the length of the encoded data, or 0 if it exceeds.
+fn emit_remainder(dst: &mut [u8], src: &[u8], next_emit: usize, mut d: usize, d_limit: usize) -> Result<usize, SnappyError> {
+    if next_emit < src.len() {
+        if d + (src.len() - next_emit) > d_limit {
+            return Ok(0)
+        }
+        d += emit_literal(&mut dst[d..], &src[next_emit..])?;
+    }
+    Ok(d)
+}
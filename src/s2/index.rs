@@ -0,0 +1,214 @@
+//! The seek index of the S2 stream format, a skippable chunk of type 0x99 at the
+//! end of a stream, which maps uncompressed offsets to the chunks holding them.
+//!
+//! After the chunk header come `s2idx\0` and signed varints of the total
+//! uncompressed and compressed lengths, the estimated uncompressed block length and
+//! the entry count, a byte telling whether uncompressed offsets are stored, the
+//! uncompressed offsets if so, and the compressed offsets of the entries. The
+//! offsets are stored as the difference to a prediction from the previous entry.
+//! The chunk ends with its length in 4 little-endian bytes and `\0xdi2s`, so it
+//! can be found from the end of the stream.
+
+/// The chunk type of the index.
+pub(crate) const CHUNK_TYPE_INDEX: u8 = 0x99;
+
+const HEADER: &[u8] = b"s2idx\x00";
+pub(crate) const TRAILER: &[u8] = b"\x00xdi2s";
+
+/// The length of the index length and the trailer at the end of the index chunk.
+pub(crate) const TRAILER_LEN: usize = 4 + 6;
+
+/// The max entry count.
+const MAX_ENTRIES: usize = 1 << 16;
+
+/// The min uncompressed distance between entries.
+const MIN_DIST: i64 = 1 << 20;
+
+/// The start of a chunk, at `compressed` bytes into the stream and `uncompressed`
+/// bytes into the decoded data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    compressed: i64,
+    uncompressed: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Index {
+    pub(crate) total_uncompressed: i64,
+    pub(crate) total_compressed: i64,
+    est_block_uncomp: i64,
+    entries: Vec<Entry>,
+}
+
+impl Index {
+    pub(crate) fn new(block_size: usize) -> Index {
+        Index {
+            total_uncompressed: -1,
+            total_compressed: -1,
+            est_block_uncomp: block_size as i64,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add the chunk starting at `compressed` and `uncompressed`, if it is far
+    /// enough from the previous entry.
+    pub(crate) fn add(&mut self, compressed: i64, uncompressed: i64) {
+        if let Some(last) = self.entries.last_mut() {
+            if last.uncompressed == uncompressed {
+                // Nothing was decoded since, so start at the later chunk.
+                last.compressed = compressed;
+                return
+            }
+            if last.uncompressed + MIN_DIST > uncompressed {
+                return
+            }
+        }
+        self.entries.push(Entry { compressed, uncompressed });
+    }
+
+    /// Keep every `n`th entry, for `n` that brings the entries to at most
+    /// `MAX_ENTRIES` and at least about 1 MiB apart.
+    fn reduce(&mut self) {
+        if self.entries.len() < MAX_ENTRIES && self.est_block_uncomp >= MIN_DIST {
+            return
+        }
+        let mut remove = (self.entries.len() + 1) / MAX_ENTRIES;
+        while self.est_block_uncomp * (remove as i64 + 1) < MIN_DIST && self.entries.len() / (remove + 1) > 1000 {
+            remove += 1;
+        }
+        self.entries = self.entries.iter().step_by(remove + 1).cloned().collect();
+        self.est_block_uncomp += self.est_block_uncomp * remove as i64;
+    }
+
+    /// Append the index chunk of a stream of `total_uncompressed` decoded bytes
+    /// in `total_compressed` bytes to `dst`.
+    pub(crate) fn append_to(&mut self, dst: &mut Vec<u8>, total_uncompressed: i64, total_compressed: i64) {
+        self.reduce();
+        let start = dst.len();
+        dst.extend_from_slice(&[CHUNK_TYPE_INDEX, 0, 0, 0]);
+        dst.extend_from_slice(HEADER);
+        put_varint(dst, total_uncompressed);
+        put_varint(dst, total_compressed);
+        put_varint(dst, self.est_block_uncomp);
+        put_varint(dst, self.entries.len() as i64);
+
+        let est = self.est_block_uncomp;
+        let predicted = self.entries.iter().enumerate()
+            .all(|(k, e)| e.uncompressed == if k == 0 { 0 } else { self.entries[k - 1].uncompressed + est });
+        dst.push(!predicted as u8);
+        if !predicted {
+            for (k, e) in self.entries.iter().enumerate() {
+                let prev = if k == 0 { 0 } else { self.entries[k - 1].uncompressed + est };
+                put_varint(dst, e.uncompressed - prev);
+            }
+        }
+        let mut c_predict = est / 2;
+        for (k, e) in self.entries.iter().enumerate() {
+            let mut delta = e.compressed;
+            if k > 0 {
+                delta -= self.entries[k - 1].compressed + c_predict;
+                // Move the prediction by half the error.
+                c_predict += delta / 2;
+            }
+            put_varint(dst, delta);
+        }
+
+        let len = dst.len() - start + TRAILER_LEN;
+        dst.extend_from_slice(&(len as u32).to_le_bytes());
+        dst.extend_from_slice(TRAILER);
+        let chunk_len = dst.len() - start - 4;
+        dst[start + 1] = chunk_len as u8;
+        dst[start + 2] = (chunk_len >> 8) as u8;
+        dst[start + 3] = (chunk_len >> 16) as u8;
+    }
+
+    /// Parse the index chunk `src`, header and trailer included.
+    /// Return `None` if it is not a valid index.
+    pub(crate) fn load(src: &[u8]) -> Option<Index> {
+        if src.len() < 4 + HEADER.len() + TRAILER_LEN || src[0] != CHUNK_TYPE_INDEX {
+            return None
+        }
+        let chunk_len = src[1] as usize | (src[2] as usize) << 8 | (src[3] as usize) << 16;
+        let mut b = &src[4..];
+        if b.len() < chunk_len || &b[..HEADER.len()] != HEADER {
+            return None
+        }
+        b = &b[HEADER.len()..];
+        let total_uncompressed = read_varint(&mut b).filter(|&v| v >= 0)?;
+        let total_compressed = read_varint(&mut b)?;
+        let est_block_uncomp = read_varint(&mut b).filter(|&v| v >= 0)?;
+        let n = read_varint(&mut b).filter(|&v| (0..=MAX_ENTRIES as i64).contains(&v))? as usize;
+        let (&has_uncompressed, rest) = b.split_first()?;
+        b = rest;
+        if has_uncompressed > 1 {
+            return None
+        }
+
+        let mut entries = vec![Entry { compressed: 0, uncompressed: 0 }; n];
+        for k in 0..n {
+            let mut off = if has_uncompressed == 1 { read_varint(&mut b)? } else { 0 };
+            if k > 0 {
+                let prev = entries[k - 1].uncompressed;
+                off = off.checked_add(prev)?.checked_add(est_block_uncomp)?;
+                if off <= prev {
+                    return None
+                }
+            }
+            if off < 0 {
+                return None
+            }
+            entries[k].uncompressed = off;
+        }
+        let mut c_predict = est_block_uncomp / 2;
+        for k in 0..n {
+            let mut off = read_varint(&mut b)?;
+            if k > 0 {
+                let next_predict = c_predict.checked_add(off / 2)?;
+                let prev = entries[k - 1].compressed;
+                off = off.checked_add(prev)?.checked_add(c_predict)?;
+                if off <= prev {
+                    return None
+                }
+                c_predict = next_predict;
+            }
+            if off < 0 {
+                return None
+            }
+            entries[k].compressed = off;
+        }
+        if b.len() < TRAILER_LEN || &b[4..TRAILER_LEN] != TRAILER {
+            return None
+        }
+        Some(Index { total_uncompressed, total_compressed, est_block_uncomp, entries })
+    }
+
+    /// Return the compressed and uncompressed offsets of the last entry at or
+    /// before the uncompressed `offset`, or of the start of the stream if none is.
+    pub(crate) fn find(&self, offset: i64) -> (i64, i64) {
+        let n = self.entries.partition_point(|e| e.uncompressed <= offset);
+        match n {
+            0 => (0, 0),
+            n => (self.entries[n - 1].compressed, self.entries[n - 1].uncompressed)
+        }
+    }
+}
+
+/// Append `v` as a zig-zag encoded varint, like Go's `binary.AppendVarint`.
+fn put_varint(dst: &mut Vec<u8>, v: i64) {
+    let mut x = ((v << 1) ^ (v >> 63)) as u64;
+    while x >= 0x80 {
+        dst.push(x as u8 | 0x80);
+        x >>= 7;
+    }
+    dst.push(x as u8);
+}
+
+/// Read a zig-zag encoded varint from the start of `b` and advance `b` past it.
+fn read_varint(b: &mut &[u8]) -> Option<i64> {
+    let (x, n) = crate::binary::uvarint(b);
+    if n <= 0 {
+        return None
+    }
+    *b = &b[n as usize..];
+    Some((x >> 1) as i64 ^ -((x & 1) as i64))
+}
@@ -0,0 +1,183 @@
+//! The block format of [S2](https://github.com/klauspost/compress/tree/master/s2),
+//! an extension of Snappy.
+//!
+//! S2 adds repeats to the Snappy block format: a copy1 with offset 0 copies from
+//! the offset of the previous copy, with lengths 9 to 11 taking 1 to 3 more bytes
+//! for lengths up to 16 MiB. Every Snappy block is an S2 block, so `decode`
+//! decodes both. S2 blocks are not Snappy blocks in general.
+//!
+//! `encode` keeps its 64 KiB hash table on the stack, `encode_with_scratch` in a
+//! caller-provided `EncodeScratch`. Both encode the whole input as one block.
+//! Split large inputs; the stream format uses blocks of up to 4 MiB.
+//!
+//! With `std`, `S2Writer` and `S2Reader` write and read the S2 stream format,
+//! whose seek index lets `S2Reader` seek in the decoded data. `S2Reader` reads
+//! Snappy framed streams too.
+//!
+//! # Examples:
+//!
+//! ```rust
+//! use xsnappy::s2;
+//!
+//! let src = b"hello world! hello world! hello world! hello world!";
+//! let mut dst = vec![0; s2::max_encode_len(src.len())];
+//! let len = s2::encode(&mut dst, src);
+//!
+//! let mut decoded = vec![0; s2::decode_len(&dst[..len]).unwrap()];
+//! s2::decode(&mut decoded, &dst[..len]).unwrap();
+//! assert_eq!(&decoded[..], &src[..]);
+//! ```
+
+mod encode;
+#[cfg(feature = "std")]
+mod index;
+#[cfg(feature = "std")]
+mod stream;
+
+use crate::binary::put_uvarint;
+use crate::decode::{_decode, _decode_len, DecodeOptions};
+use crate::encode::{emit_literal, literal_extra_size};
+use crate::error::SnappyError;
+use encode::{encode_block, MIN_NON_LITERAL_BLOCK_SIZE, TABLE_SIZE};
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "alloc")]
+use crate::vec::append_with;
+pub use encode::EncodeScratch;
+#[cfg(feature = "std")]
+pub use stream::{S2Writer, S2Reader, WriterOptions, DEFAULT_BLOCK_SIZE, MIN_BLOCK_SIZE, MAX_BLOCK_SIZE};
+
+/// Return the max length of the S2 encoded data of `src_len` bytes,
+/// or 0 if `src_len` is too large to encode.
+pub fn max_encode_len(src_len: usize) -> usize {
+    if src_len as u64 > 0xffffffff {
+        return 0
+    }
+    let mut header = [0; 10];
+    let n = src_len as u64 + put_uvarint(&mut header, src_len as u64) as u64
+        + literal_extra_size(src_len) as u64;
+    if n > 0xffffffff {
+        return 0
+    }
+    n as usize
+}
+
+/// Encode `src` to `dst` as an S2 block. The `dst` must be initialized with a certain length.
+/// Return the exact length of encoded data.
+/// # Panics:
+/// Panics if the length of `dst` is less then `max_encode_len(src.len())`, or if `src` is too large.
+pub fn encode(dst: &mut [u8], src: &[u8]) -> usize {
+    let n = max_encode_len(src.len());
+    if n == 0 {
+        panic!("snappy: encode block is too large")
+    } else if dst.len() < n {
+        panic!("snappy: dst len is too small")
+    }
+    let mut table = [0; TABLE_SIZE];
+    encode_with_table(dst, src, &mut table).expect("snappy: dst len is too small")
+}
+
+/// Encode `src` to `dst` like `encode`, but with the hash table of `scratch` instead
+/// of one on the stack. The output is the same as `encode`'s. Return
+/// `SnappyError::EncodeTooLarge` if `src` is too large, and `SnappyError::DstTooSmall`
+/// if the length of `dst` is less than `max_encode_len(src.len())`.
+/// # Examples:
+///
+/// ```rust
+/// use xsnappy::s2;
+///
+/// let mut scratch = Box::new(s2::EncodeScratch::new());
+/// let src = b"hello world! hello world! hello world! hello world!";
+/// let mut dst = vec![0; s2::max_encode_len(src.len())];
+/// let len = s2::encode_with_scratch(&mut dst, src, &mut scratch).unwrap();
+/// let mut decoded = [0; 51];
+/// assert_eq!(s2::decode(&mut decoded, &dst[..len]), Ok(src.len()));
+/// assert_eq!(&decoded[..], &src[..]);
+/// ```
+pub fn encode_with_scratch(dst: &mut [u8], src: &[u8], scratch: &mut EncodeScratch) -> Result<usize, SnappyError> {
+    encode_with_table(dst, src, &mut scratch.table)
+}
+
+/// Encode `src` to `dst` using `table` of `TABLE_SIZE` entries as the hash table.
+fn encode_with_table(dst: &mut [u8], src: &[u8], table: &mut [u32]) -> Result<usize, SnappyError> {
+    let n = max_encode_len(src.len());
+    if n == 0 {
+        return Err(SnappyError::EncodeTooLarge)
+    } else if dst.len() < n {
+        return Err(SnappyError::DstTooSmall)
+    }
+    let mut d = put_uvarint(dst, src.len() as u64);
+    if src.len() < MIN_NON_LITERAL_BLOCK_SIZE {
+        return Ok(d + emit_literal(&mut dst[d..], src)?)
+    }
+    match encode_block(&mut dst[d..], src, table)? {
+        0 => d += emit_literal(&mut dst[d..], src)?,
+        n => d += n
+    }
+    Ok(d)
+}
+
+/// Return the exact length of decoded data.
+pub fn decode_len(src: &[u8]) -> Result<usize, SnappyError> {
+    crate::decode::decode_len(src)
+}
+
+/// Decode the S2 or Snappy block `src` to `dst`. Return the length of decoded data.
+pub fn decode(dst: &mut [u8], src: &[u8]) -> Result<usize, SnappyError> {
+    let (d_len, s) = _decode_len(src)?;
+    if dst.len() < d_len {
+        return Err(SnappyError::DstTooSmall)
+    }
+    _decode::<true>(&mut dst[..d_len], src, s)?;
+    Ok(d_len)
+}
+
+/// Decode `src` to `dst` like `decode`, if it is within the limits of `options`.
+/// Return `SnappyError::LimitExceeded` otherwise.
+pub fn decode_with_options(dst: &mut [u8], src: &[u8], options: &DecodeOptions) -> Result<usize, SnappyError> {
+    options.check(decode_len(src)?, src)?;
+    decode(dst, src)
+}
+
+/// Encode `src` as an S2 block and return the encoded data.
+#[cfg(feature = "alloc")]
+pub fn compress_to_vec(src: &[u8]) -> Result<Vec<u8>, SnappyError> {
+    let n = max_encode_len(src.len());
+    if n == 0 {
+        return Err(SnappyError::EncodeTooLarge)
+    }
+    let mut dst = Vec::new();
+    let mut table = vec![0; TABLE_SIZE];
+    append_with(&mut dst, n, |buf| encode_with_table(buf, src, &mut table))?;
+    Ok(dst)
+}
+
+/// Decode the S2 or Snappy block `src` and return the decoded data. It allocates
+/// as much as the header of `src` claims, up to 4 GiB, so decode untrusted data
+/// with `decompress_to_vec_with_options`.
+#[cfg(feature = "alloc")]
+pub fn decompress_to_vec(src: &[u8]) -> Result<Vec<u8>, SnappyError> {
+    decompress_to_vec_with_options(src, &DecodeOptions::default())
+}
+
+/// Decode the S2 or Snappy block `src` and return the decoded data, if it is within
+/// the limits of `options`. The limits are checked before anything is allocated.
+/// # Examples:
+///
+/// ```rust
+/// use xsnappy::{s2, DecodeOptions};
+/// use xsnappy::error::SnappyError;
+///
+/// // The header claims 4 GiB of decoded data.
+/// let bomb = [0xff, 0xff, 0xff, 0xff, 0x0f, 0x00];
+/// let options = DecodeOptions { max_decoded_len: Some(1 << 20), max_ratio: None };
+/// assert_eq!(s2::decompress_to_vec_with_options(&bomb, &options), Err(SnappyError::LimitExceeded));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn decompress_to_vec_with_options(src: &[u8], options: &DecodeOptions) -> Result<Vec<u8>, SnappyError> {
+    let n = decode_len(src)?;
+    options.check(n, src)?;
+    let mut dst = Vec::new();
+    append_with(&mut dst, n, |buf| decode(buf, src))?;
+    Ok(dst)
+}
@@ -0,0 +1,523 @@
+use super::encode::{encode_block, MIN_NON_LITERAL_BLOCK_SIZE, TABLE_SIZE};
+use super::index::{Index, TRAILER, TRAILER_LEN};
+use super::{decode, decode_len, max_encode_len};
+use crate::binary::put_uvarint;
+use crate::crc32c::{crc32c, mask};
+use crate::decode::DecodeOptions;
+use crate::error::{SnappyError, CorruptKind};
+use crate::frame::{self, parse_chunk_header, put_chunk_header, CHECKSUM_SIZE, CHUNK_HEADER_SIZE,
+                   CHUNK_PREFIX_LEN, CHUNK_TYPE_COMPRESSED_DATA, CHUNK_TYPE_PADDING, CHUNK_TYPE_STREAM_IDENTIFIER,
+                   CHUNK_TYPE_UNCOMPRESSED_DATA, MAGIC_BODY, MAX_ENCODED_LEN_OF_MAX_BLOCK_SIZE};
+use crate::stream::{read_full, BlockDecoder, BlockEncoder, BlockReader, BlockWriter, Decoded};
+use std::io::{self, Read, BufRead, Write, Seek, SeekFrom};
+
+/// The default uncompressed length of a chunk, 1 MiB.
+pub const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
+
+/// The smallest block size.
+pub const MIN_BLOCK_SIZE: usize = 4 << 10;
+
+/// The largest uncompressed length of a chunk in an S2 stream, 4 MiB.
+pub const MAX_BLOCK_SIZE: usize = 4 << 20;
+
+const S2_MAGIC_BODY: &[u8] = b"S2sTwO";
+const S2_MAGIC_CHUNK: &[u8] = b"\xff\x06\x00\x00S2sTwO";
+
+/// The options of an `S2Writer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriterOptions {
+    /// The uncompressed length of a chunk, from `MIN_BLOCK_SIZE` to `MAX_BLOCK_SIZE`.
+    pub block_size: usize,
+    /// Whether to end the stream with the seek index.
+    pub index: bool,
+}
+
+impl Default for WriterOptions {
+    fn default() -> WriterOptions {
+        WriterOptions { block_size: DEFAULT_BLOCK_SIZE, index: true }
+    }
+}
+
+/// Write one chunk holding `src` to `dst`, header and checksum included, using
+/// `table` as the hash table. The chunk is stored uncompressed if `encode_block`
+/// gives up on it. `dst` must be at least `CHUNK_PREFIX_LEN + max_encode_len(src.len())` long.
+/// Return the length of the chunk.
+fn compress_chunk(dst: &mut [u8], src: &[u8], table: &mut [u32]) -> usize {
+    let checksum = mask(crc32c(src));
+    let body = &mut dst[CHUNK_PREFIX_LEN..];
+    let h = put_uvarint(body, src.len() as u64);
+    let n = match src.len() {
+        len if len < MIN_NON_LITERAL_BLOCK_SIZE => 0,
+        _ => encode_block(&mut body[h..], src, table).expect("snappy: dst len is too small")
+    };
+    let (chunk_type, n) = match n {
+        0 => {
+            body[..src.len()].copy_from_slice(src);
+            (CHUNK_TYPE_UNCOMPRESSED_DATA, src.len())
+        }
+        n => (CHUNK_TYPE_COMPRESSED_DATA, h + n)
+    };
+    put_chunk_header(dst, chunk_type, CHECKSUM_SIZE + n);
+    dst[4..8].copy_from_slice(&checksum.to_le_bytes());
+    CHUNK_PREFIX_LEN + n
+}
+
+/// A writer that compresses data to the S2 stream format.
+///
+/// The stream is the Snappy framing format with the stream identifier `S2sTwO`,
+/// S2 blocks and chunks of up to 4 MiB. Written data is buffered and emitted as a
+/// chunk whenever `block_size` bytes are buffered, or on `flush`.
+///
+/// Unless disabled in the options, the stream ends with the seek index, a skippable
+/// chunk that `S2Reader` uses to seek. The index is written by `into_inner`, or when
+/// the writer is dropped, with errors ignored there. Writers without the index still
+/// write streams that S2 readers seek in, by decoding from the start.
+///
+/// # Examples:
+///
+/// ```rust
+/// use std::io::{Read, Seek, SeekFrom, Write};
+/// use xsnappy::s2::{S2Reader, S2Writer};
+///
+/// let mut w = S2Writer::new(vec![]);
+/// w.write_all(b"hello world! hello world!").unwrap();
+/// let stream = w.into_inner().unwrap();
+///
+/// let mut r = S2Reader::new(std::io::Cursor::new(stream));
+/// r.seek(SeekFrom::Start(13)).unwrap();
+/// let mut dst = String::new();
+/// r.read_to_string(&mut dst).unwrap();
+/// assert_eq!(dst, "hello world!");
+/// ```
+pub struct S2Writer<W: Write> {
+    inner: BlockWriter<W, S2Encoder>,
+}
+
+/// Compresses a block to a chunk, after the stream identifier for the first one,
+/// and keeps the index of the chunks.
+struct S2Encoder {
+    table: Vec<u32>,
+    out: Vec<u8>,
+    index: Option<Index>,
+    wrote_stream_header: bool,
+    /// The length of the stream written so far.
+    written: usize,
+    /// The length of the data written so far.
+    uncompressed: usize,
+    /// The index chunk that ends the stream.
+    index_chunk: Vec<u8>,
+}
+
+impl BlockEncoder for S2Encoder {
+    fn encode(&mut self, src: &[u8]) -> io::Result<&[u8]> {
+        if src.is_empty() {
+            return Ok(&[])
+        }
+        let mut n = 0;
+        if !self.wrote_stream_header {
+            self.out[..S2_MAGIC_CHUNK.len()].copy_from_slice(S2_MAGIC_CHUNK);
+            n = S2_MAGIC_CHUNK.len();
+        }
+        if let Some(index) = &mut self.index {
+            index.add((self.written + n) as i64, self.uncompressed as i64);
+        }
+        n += compress_chunk(&mut self.out[n..], src, &mut self.table);
+        self.wrote_stream_header = true;
+        self.written += n;
+        self.uncompressed += src.len();
+        Ok(&self.out[..n])
+    }
+
+    /// Return the index, if any.
+    fn finish(&mut self) -> &[u8] {
+        if let Some(mut index) = self.index.take() {
+            if self.wrote_stream_header {
+                index.append_to(&mut self.index_chunk, self.uncompressed as i64, self.written as i64);
+                self.written += self.index_chunk.len();
+            }
+        }
+        &self.index_chunk
+    }
+}
+
+impl<W: Write> S2Writer<W> {
+    /// Create a writer with the default options, that writes the compressed stream to `w`.
+    pub fn new(w: W) -> S2Writer<W> {
+        S2Writer::with_options(w, WriterOptions::default())
+    }
+
+    /// Create a writer with `options`.
+    /// # Panics:
+    /// Panics if `block_size` is not from `MIN_BLOCK_SIZE` to `MAX_BLOCK_SIZE`.
+    pub fn with_options(w: W, options: WriterOptions) -> S2Writer<W> {
+        assert!((MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&options.block_size),
+                "snappy: s2 block size must be from {} to {}", MIN_BLOCK_SIZE, MAX_BLOCK_SIZE);
+        let enc = S2Encoder {
+            table: vec![0; TABLE_SIZE],
+            out: vec![0; S2_MAGIC_CHUNK.len() + CHUNK_PREFIX_LEN + max_encode_len(options.block_size)],
+            index: if options.index { Some(Index::new(options.block_size)) } else { None },
+            wrote_stream_header: false,
+            written: 0,
+            uncompressed: 0,
+            index_chunk: Vec::new(),
+        };
+        S2Writer { inner: BlockWriter::new(w, options.block_size, enc) }
+    }
+
+    /// Flush the buffered data, write the seek index and return the underlying writer.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.inner.into_inner()
+    }
+
+    /// Return a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.get_ref()
+    }
+
+    /// Return a mutable reference to the underlying writer.
+    /// Writing to it directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.get_mut()
+    }
+}
+
+impl<W: Write> Write for S2Writer<W> {
+    fn write(&mut self, p: &[u8]) -> io::Result<usize> {
+        self.inner.write(p)
+    }
+
+    /// Emit the buffered data as a chunk and flush the underlying writer.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader that decompresses the S2 stream format, and the Snappy framing
+/// format like S2 readers do.
+///
+/// Every chunk is verified against its checksum. Chunks may hold up to 4 MiB in
+/// an S2 stream and up to 64 KiB in a Snappy stream, longer ones are rejected with
+/// `CorruptKind::ChunkLength`. Padding, the seek index and other skippable chunks
+/// are skipped, reserved unskippable chunks are rejected with
+/// `SnappyError::UnskippableChunk`. An error in the stream is returned by every
+/// later read.
+///
+/// It implements `Seek` when `r` does. The first seek reads the seek index from
+/// the end of the stream, and later seeks start decoding from the last indexed
+/// chunk before the target. Without an index, seeking backwards decodes from the
+/// start of the stream, and seeking from the end decodes the whole stream once.
+/// Seeking past the end fails with `io::ErrorKind::UnexpectedEof`.
+///
+/// # Examples:
+///
+/// ```rust
+/// use std::io::{Read, Write};
+/// use xsnappy::FrameEncoder;
+/// use xsnappy::s2::S2Reader;
+///
+/// let mut enc = FrameEncoder::new(vec![]);
+/// enc.write_all(b"hello world! hello world!").unwrap();
+/// let framed = enc.into_inner().unwrap();
+///
+/// let mut dst = vec![];
+/// S2Reader::new(&framed[..]).read_to_end(&mut dst).unwrap();
+/// assert_eq!(dst, b"hello world! hello world!");
+/// ```
+pub struct S2Reader<R: Read> {
+    inner: BlockReader<R, S2Decoder>,
+}
+
+/// Reads the chunks of an S2 or Snappy stream, and the seek index.
+struct S2Decoder {
+    options: DecodeOptions,
+    body: Vec<u8>,
+    read_stream_header: bool,
+    /// Whether the stream identifier is Snappy's.
+    snappy: bool,
+    /// The offset of the stream in the underlying reader, once seeking needed it.
+    base: Option<u64>,
+    index: Option<Index>,
+}
+
+impl<R: Read> S2Reader<R> {
+    /// Create a reader that reads the compressed stream from `r`.
+    pub fn new(r: R) -> S2Reader<R> {
+        S2Reader::with_options(r, DecodeOptions::default())
+    }
+
+    /// Create a reader that fails with `SnappyError::LimitExceeded` once the stream
+    /// decodes to more than `max_decoded_len` bytes in total, or a compressed chunk
+    /// exceeds `max_ratio`.
+    pub fn with_options(r: R, options: DecodeOptions) -> S2Reader<R> {
+        let dec = S2Decoder {
+            options,
+            body: Vec::new(),
+            read_stream_header: false,
+            snappy: false,
+            base: None,
+            index: None,
+        };
+        S2Reader { inner: BlockReader::new(r, dec) }
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner.r
+    }
+
+    /// Return a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner.r
+    }
+
+    /// Return a mutable reference to the underlying reader.
+    /// Reading from it directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner.r
+    }
+
+    /// Decode and discard `n` bytes. Fail with `io::ErrorKind::UnexpectedEof`
+    /// if the stream ends first.
+    fn skip(&mut self, mut n: u64) -> io::Result<()> {
+        while n > 0 {
+            let m = std::cmp::min(self.fill_buf()?.len() as u64, n);
+            if m == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "snappy: seek past the end of the stream"))
+            }
+            self.consume(m as usize);
+            n -= m;
+        }
+        Ok(())
+    }
+}
+
+impl S2Decoder {
+    /// Return the max uncompressed length of a chunk in the current stream.
+    fn max_block_size(&self) -> usize {
+        if self.snappy { frame::MAX_BLOCK_SIZE } else { MAX_BLOCK_SIZE }
+    }
+
+    /// Check a chunk header. Return true if the chunk body should be decoded,
+    /// false if it should be skipped.
+    fn check_chunk(&self, chunk_type: u8, len: usize) -> Result<bool, SnappyError> {
+        if !self.read_stream_header && chunk_type != CHUNK_TYPE_STREAM_IDENTIFIER {
+            return Err(frame::corrupt(CorruptKind::StreamIdentifier))
+        }
+        let max_len = match chunk_type {
+            CHUNK_TYPE_COMPRESSED_DATA if self.snappy => MAX_ENCODED_LEN_OF_MAX_BLOCK_SIZE,
+            CHUNK_TYPE_COMPRESSED_DATA => max_encode_len(MAX_BLOCK_SIZE),
+            CHUNK_TYPE_UNCOMPRESSED_DATA => self.max_block_size(),
+            CHUNK_TYPE_STREAM_IDENTIFIER => {
+                if len != S2_MAGIC_BODY.len() {
+                    return Err(frame::corrupt(CorruptKind::ChunkLength))
+                }
+                return Ok(true)
+            }
+            // Reserved unskippable chunks (chunk types 0x02-0x7f).
+            0x02..=0x7f => return Err(SnappyError::UnskippableChunk),
+            // Padding (chunk type 0xfe), the index (chunk type 0x99) and other skippable chunks.
+            CHUNK_TYPE_PADDING | 0x80..=0xfd => return Ok(false)
+        };
+        if !(CHECKSUM_SIZE..=CHECKSUM_SIZE + max_len).contains(&len) {
+            return Err(frame::corrupt(CorruptKind::ChunkLength))
+        }
+        Ok(true)
+    }
+
+    /// Decode the data chunk of `chunk_type` in `body`, which starts at input offset `pos`.
+    fn decode_body(&mut self, d: &mut Decoded, chunk_type: u8, pos: usize) -> Result<(), SnappyError> {
+        let decoded_len = d.decoded_len;
+        let checksum = u32::from_le_bytes([self.body[0], self.body[1], self.body[2], self.body[3]]);
+        let body = &self.body[CHECKSUM_SIZE..];
+        let n;
+        if chunk_type == CHUNK_TYPE_COMPRESSED_DATA {
+            n = decode_len(body).map_err(|err| err.shift(pos + CHUNK_PREFIX_LEN, decoded_len))?;
+            if n > self.max_block_size() {
+                return Err(d.corrupt(CorruptKind::ChunkLength, pos))
+            }
+            self.options.check_len(decoded_len.saturating_add(n))?;
+            self.options.check_ratio(n, body.len())?;
+            d.buf.resize(n, 0);
+            decode(&mut d.buf, body).map_err(|err| err.shift(pos + CHUNK_PREFIX_LEN, decoded_len))?;
+        } else {
+            n = body.len();
+            self.options.check_len(decoded_len.saturating_add(n))?;
+            d.buf.clear();
+            d.buf.extend_from_slice(body);
+        }
+        if mask(crc32c(&d.buf)) != checksum {
+            return Err(d.corrupt(CorruptKind::Checksum, pos))
+        }
+        d.decoded(n);
+        Ok(())
+    }
+}
+
+impl<R: Read> BlockDecoder<R> for S2Decoder {
+    /// Read and decode the next data chunk, skipping the others.
+    /// Return false at the end of the stream.
+    fn next_block(&mut self, r: &mut R, d: &mut Decoded) -> io::Result<bool> {
+        loop {
+            let pos = d.pos;
+            let mut header = [0; CHUNK_HEADER_SIZE];
+            match read_full(r, &mut header)? {
+                0 => return Ok(false),
+                CHUNK_HEADER_SIZE => {}
+                _ => return Err(d.fail(d.corrupt(CorruptKind::TruncatedChunk, pos)))
+            }
+            let (chunk_type, len) = parse_chunk_header(&header);
+            match self.check_chunk(chunk_type, len) {
+                Ok(true) => {}
+                Ok(false) => {
+                    if io::copy(&mut r.by_ref().take(len as u64), &mut io::sink())? < len as u64 {
+                        return Err(d.fail(d.corrupt(CorruptKind::TruncatedChunk, pos)))
+                    }
+                    d.pos += CHUNK_HEADER_SIZE + len;
+                    continue
+                }
+                Err(err) => return Err(d.fail(err.shift(pos, d.decoded_len)))
+            }
+            self.body.clear();
+            if r.by_ref().take(len as u64).read_to_end(&mut self.body)? < len {
+                return Err(d.fail(d.corrupt(CorruptKind::TruncatedChunk, pos)))
+            }
+            d.pos += CHUNK_HEADER_SIZE + len;
+            if chunk_type == CHUNK_TYPE_STREAM_IDENTIFIER {
+                match &self.body[..] {
+                    S2_MAGIC_BODY => self.snappy = false,
+                    MAGIC_BODY => self.snappy = true,
+                    _ => return Err(d.fail(d.corrupt(CorruptKind::StreamIdentifier, pos)))
+                }
+                self.read_stream_header = true;
+                continue
+            }
+            if let Err(err) = self.decode_body(d, chunk_type, pos) {
+                return Err(d.fail(err))
+            }
+            return Ok(true)
+        }
+    }
+}
+
+impl<R: Read + Seek> S2Reader<R> {
+    /// Return the offset of the stream in the underlying reader, reading the
+    /// seek index at the end of the stream on the first call.
+    fn init_seek(&mut self) -> io::Result<u64> {
+        if let Some(base) = self.inner.dec.base {
+            return Ok(base)
+        }
+        let r = &mut self.inner.r;
+        let here = r.stream_position()?;
+        let base = here - self.inner.d.pos as u64;
+        let end = r.seek(SeekFrom::End(0))?;
+        self.inner.dec.index = read_index(r, base, end)?;
+        r.seek(SeekFrom::Start(here))?;
+        self.inner.dec.base = Some(base);
+        Ok(base)
+    }
+
+    /// Return the total length of the decoded data, from the index if any.
+    fn total_len(&mut self) -> io::Result<u64> {
+        if let Some(index) = &self.inner.dec.index {
+            return Ok(index.total_uncompressed as u64)
+        }
+        loop {
+            let n = self.fill_buf()?.len();
+            if n == 0 {
+                return Ok(self.inner.d.decoded_len as u64)
+            }
+            self.consume(n);
+        }
+    }
+}
+
+/// Read the index chunk that ends the stream from `base` to `end` in `r`, if there is
+/// a valid one for this stream.
+fn read_index<R: Read + Seek>(r: &mut R, base: u64, end: u64) -> io::Result<Option<Index>> {
+    let stream_len = end.saturating_sub(base);
+    if stream_len < TRAILER_LEN as u64 {
+        return Ok(None)
+    }
+    let mut trailer = [0; TRAILER_LEN];
+    r.seek(SeekFrom::Start(end - TRAILER_LEN as u64))?;
+    r.read_exact(&mut trailer)?;
+    if &trailer[4..] != TRAILER {
+        return Ok(None)
+    }
+    let len = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]) as u64;
+    if len > stream_len || len > (CHUNK_HEADER_SIZE + 0xffffff) as u64 {
+        return Ok(None)
+    }
+    let mut chunk = vec![0; len as usize];
+    r.seek(SeekFrom::Start(end - len))?;
+    r.read_exact(&mut chunk)?;
+    // The index of another stream, like the last of concatenated streams, doesn't count.
+    Ok(Index::load(&chunk).filter(|index| {
+        index.total_compressed < 0 || index.total_compressed as u64 == stream_len - len
+    }))
+}
+
+impl<R: Read> Read for S2Reader<R> {
+    fn read(&mut self, p: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(p)
+    }
+}
+
+impl<R: Read> BufRead for S2Reader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+impl<R: Read + Seek> Seek for S2Reader<R> {
+    /// Seek to an offset in the decoded data.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let base = self.init_seek()?;
+        let target = match pos {
+            SeekFrom::Start(n) => n as i128,
+            SeekFrom::Current(n) => self.inner.d.position() as i128 + n as i128,
+            SeekFrom::End(n) => self.total_len()? as i128 + n as i128,
+        };
+        if target < 0 || target > u64::MAX as i128 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "snappy: invalid seek to a negative or overflowing position"))
+        }
+        let target = target as u64;
+        if let Some(index) = &self.inner.dec.index {
+            if target > index.total_uncompressed as u64 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "snappy: seek past the end of the stream"))
+            }
+        }
+
+        // Within the current chunk.
+        let d = &mut self.inner.d;
+        let chunk_start = (d.decoded_len - d.j) as u64;
+        if chunk_start <= target && target <= d.decoded_len as u64 {
+            d.i = (target - chunk_start) as usize;
+            return Ok(target)
+        }
+
+        // `total_len` may have decoded to the end, so take the position now.
+        let start = match &self.inner.dec.index {
+            Some(index) => Some(index.find(target as i64)),
+            None if target < d.position() as u64 || d.err.is_some() => Some((0, 0)),
+            None => None
+        };
+        if let Some((compressed, uncompressed)) = start {
+            self.inner.r.seek(SeekFrom::Start(base + compressed as u64))?;
+            self.inner.d.restart(compressed as usize, uncompressed as usize);
+            // Indexed chunks are in an S2 stream, past its identifier.
+            self.inner.dec.read_stream_header = compressed > 0;
+            if compressed > 0 {
+                self.inner.dec.snappy = false;
+            }
+        }
+        let current = self.inner.d.position() as u64;
+        self.skip(target - current)?;
+        Ok(target)
+    }
+}
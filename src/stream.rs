@@ -0,0 +1,235 @@
+//! The scaffolding of the writers and readers of the block stream formats,
+//! the `hadoop` and `xerial` modules and the S2 stream: buffering written data
+//! to blocks, the decoded data of a reader, and the errors that stick.
+
+use crate::error::{SnappyError, CorruptKind};
+use std::io::{self, Read, Write};
+
+/// Read from `r` until `buf` is full or `r` ends. Return the length read.
+pub(crate) fn read_full<R: Read>(r: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(m) => n += m,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e)
+        }
+    }
+    Ok(n)
+}
+
+/// Return a copy of `err`, to return a kept error again.
+fn copy_err(err: &io::Error) -> io::Error {
+    io::Error::new(err.kind(), err.to_string())
+}
+
+/// Turns the blocks of a `BlockWriter` into the bytes of a stream format.
+pub(crate) trait BlockEncoder {
+    /// Encode the block `src` and return the bytes to write out. `src` is empty
+    /// when there is nothing buffered to flush.
+    fn encode(&mut self, src: &[u8]) -> io::Result<&[u8]>;
+
+    /// Return the bytes that end the stream, written by `finish`.
+    fn finish(&mut self) -> &[u8] {
+        &[]
+    }
+}
+
+/// Buffers written data and emits it in blocks of `block_size` bytes through `E`.
+///
+/// A failed write to the underlying writer is kept and returned by every later call.
+/// When dropped, the buffered data is emitted and the stream finished, with errors ignored.
+pub(crate) struct BlockWriter<W: Write, E: BlockEncoder> {
+    w: Option<W>,
+    err: Option<io::Error>,
+    buf: Vec<u8>,
+    block_size: usize,
+    pub(crate) enc: E,
+}
+
+impl<W: Write, E: BlockEncoder> BlockWriter<W, E> {
+    pub(crate) fn new(w: W, block_size: usize, enc: E) -> BlockWriter<W, E> {
+        BlockWriter { w: Some(w), err: None, buf: Vec::with_capacity(block_size), block_size, enc }
+    }
+
+    /// Finish the stream and return the underlying writer.
+    pub(crate) fn into_inner(mut self) -> io::Result<W> {
+        self.finish()?;
+        let mut w = self.w.take().unwrap();
+        w.flush()?;
+        Ok(w)
+    }
+
+    pub(crate) fn get_ref(&self) -> &W {
+        self.w.as_ref().unwrap()
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut W {
+        self.w.as_mut().unwrap()
+    }
+
+    /// Return the error of an earlier failed write, if any.
+    fn check_err(&self) -> io::Result<()> {
+        match &self.err {
+            Some(err) => Err(copy_err(err)),
+            None => Ok(())
+        }
+    }
+
+    /// Write `data` to the underlying writer, keeping a failure.
+    fn write_out(w: &mut W, err: &mut Option<io::Error>, data: &[u8]) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(())
+        }
+        if let Err(e) = w.write_all(data) {
+            let ret = copy_err(&e);
+            *err = Some(e);
+            return Err(ret)
+        }
+        Ok(())
+    }
+
+    /// Emit the buffered data as a block.
+    fn flush_buf(&mut self) -> io::Result<()> {
+        self.check_err()?;
+        let data = self.enc.encode(&self.buf)?;
+        BlockWriter::<W, E>::write_out(self.w.as_mut().unwrap(), &mut self.err, data)?;
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Emit the buffered data and the end of the stream.
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush_buf()?;
+        let data = self.enc.finish();
+        BlockWriter::<W, E>::write_out(self.w.as_mut().unwrap(), &mut self.err, data)
+    }
+
+    pub(crate) fn write(&mut self, p: &[u8]) -> io::Result<usize> {
+        self.check_err()?;
+        let mut n = 0;
+        while n < p.len() {
+            let m = std::cmp::min(p.len() - n, self.block_size - self.buf.len());
+            self.buf.extend_from_slice(&p[n..n + m]);
+            n += m;
+            if self.buf.len() == self.block_size {
+                self.flush_buf()?;
+            }
+        }
+        Ok(n)
+    }
+
+    /// Emit the buffered data and flush the underlying writer.
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf()?;
+        self.w.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write, E: BlockEncoder> Drop for BlockWriter<W, E> {
+    fn drop(&mut self) {
+        if self.w.is_some() {
+            let _ = self.finish();
+        }
+    }
+}
+
+/// The decoded data of a `BlockReader` and its position in the stream.
+pub(crate) struct Decoded {
+    /// The error that stopped the reader, returned by every later read.
+    pub(crate) err: Option<SnappyError>,
+    pub(crate) buf: Vec<u8>,
+    pub(crate) i: usize,
+    pub(crate) j: usize,
+    /// The input offset of the next block to read.
+    pub(crate) pos: usize,
+    /// The length of the data decoded so far.
+    pub(crate) decoded_len: usize,
+}
+
+impl Decoded {
+    /// Keep and return `err`.
+    pub(crate) fn fail(&mut self, err: SnappyError) -> io::Error {
+        self.err = Some(err);
+        err.into()
+    }
+
+    /// Return a corruption of `kind` at input offset `pos`.
+    pub(crate) fn corrupt(&self, kind: CorruptKind, pos: usize) -> SnappyError {
+        SnappyError::corrupt(kind, pos, self.decoded_len, None)
+    }
+
+    /// Make the `n` bytes decoded to `buf` the data to read next.
+    pub(crate) fn decoded(&mut self, n: usize) {
+        self.decoded_len = self.decoded_len.saturating_add(n);
+        self.i = 0;
+        self.j = n;
+    }
+
+    /// Discard the decoded data and continue at input offset `pos`, which
+    /// decodes to the data at `decoded_len`.
+    pub(crate) fn restart(&mut self, pos: usize, decoded_len: usize) {
+        self.err = None;
+        self.i = 0;
+        self.j = 0;
+        self.pos = pos;
+        self.decoded_len = decoded_len;
+    }
+
+    /// Return the position in the decoded data of the next byte to read.
+    pub(crate) fn position(&self) -> usize {
+        self.decoded_len - (self.j - self.i)
+    }
+}
+
+/// Reads and decodes the blocks of a stream format for a `BlockReader`.
+pub(crate) trait BlockDecoder<R: Read> {
+    /// Read the next block from `r` and decode it to `d`. Return false at the end of the stream.
+    /// Errors of the stream are kept with `Decoded::fail`.
+    fn next_block(&mut self, r: &mut R, d: &mut Decoded) -> io::Result<bool>;
+}
+
+/// Reads a stream of blocks through `D`, handing out the decoded data of one block at a time.
+pub(crate) struct BlockReader<R: Read, D: BlockDecoder<R>> {
+    pub(crate) r: R,
+    pub(crate) d: Decoded,
+    pub(crate) dec: D,
+}
+
+impl<R: Read, D: BlockDecoder<R>> BlockReader<R, D> {
+    pub(crate) fn new(r: R, dec: D) -> BlockReader<R, D> {
+        BlockReader {
+            r,
+            d: Decoded { err: None, buf: Vec::new(), i: 0, j: 0, pos: 0, decoded_len: 0 },
+            dec,
+        }
+    }
+
+    pub(crate) fn read(&mut self, p: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let data = self.fill_buf()?;
+            let n = std::cmp::min(p.len(), data.len());
+            p[..n].copy_from_slice(&data[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+
+    pub(crate) fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.d.i == self.d.j {
+            if let Some(err) = self.d.err {
+                return Err(err.into())
+            }
+            if !self.dec.next_block(&mut self.r, &mut self.d)? {
+                break
+            }
+        }
+        Ok(&self.d.buf[self.d.i..self.d.j])
+    }
+
+    pub(crate) fn consume(&mut self, amt: usize) {
+        self.d.i = std::cmp::min(self.d.i + amt, self.d.j);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::encode::{encode, max_encode_len};
+use crate::decode::{decode, decode_len, DecodeOptions};
+use crate::error::SnappyError;
+use alloc::vec::Vec;
+
+/// Encode `src` and append the encoded data to `dst`.
+/// Return the length of the appended data.
+pub fn compress_into(dst: &mut Vec<u8>, src: &[u8]) -> Result<usize, SnappyError> {
+    let n = max_encode_len(src.len());
+    if n == 0 {
+        return Err(SnappyError::EncodeTooLarge)
+    }
+    append_with(dst, n, |buf| Ok(encode(buf, src)))
+}
+
+/// Decode `src` and append the decoded data to `dst`, if it is within the limits of `options`.
+/// Return the length of the appended data. `dst` is unchanged on error.
+pub fn decompress_into(dst: &mut Vec<u8>, src: &[u8], options: &DecodeOptions) -> Result<usize, SnappyError> {
+    let n = decode_len(src)?;
+    options.check(n, src)?;
+    append_with(dst, n, |buf| decode(buf, src))
+}
+
+/// Append `n` zeroed bytes to `dst` and let `f` write to them. Keep the bytes
+/// `f` reports as written, or nothing on error.
+pub(crate) fn append_with<F>(dst: &mut Vec<u8>, n: usize, f: F) -> Result<usize, SnappyError>
+    where F: FnOnce(&mut [u8]) -> Result<usize, SnappyError> {
+    let len = dst.len();
+    dst.resize(len + n, 0);
+    match f(&mut dst[len..]) {
+        Ok(size) => {
+            dst.truncate(len + size);
+            Ok(size)
+        }
+        Err(err) => {
+            dst.truncate(len);
+            Err(err)
+        }
+    }
+}
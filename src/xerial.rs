@@ -0,0 +1,326 @@
+//! The stream format of xerial snappy-java's `SnappyOutputStream`, which Kafka
+//! uses for Snappy compressed message batches.
+//!
+//! A stream is a 16-byte header, the magic `\x82SNAPPY\0` followed by the
+//! big-endian 32-bit version and compatible version, both 1. Then come chunks,
+//! each a big-endian 32-bit length and a Snappy block. Streams may be
+//! concatenated, so a header may also appear where a chunk is expected.
+//! There are no checksums.
+
+use crate::encode::{max_encode_len, Encoder};
+use crate::decode::{decode, decode_len, max_decode_len, DecodeOptions};
+use crate::error::{SnappyError, CorruptKind};
+use crate::stream::{read_full, BlockDecoder, BlockEncoder, BlockReader, BlockWriter, Decoded};
+use std::io::{self, Read, BufRead, Write};
+
+/// The magic at the start of a stream.
+pub const MAGIC: &[u8] = b"\x82SNAPPY\x00";
+
+/// The header of a stream, the magic with version 1 and compatible version 1.
+const HEADER: &[u8; HEADER_LEN] = b"\x82SNAPPY\x00\x00\x00\x00\x01\x00\x00\x00\x01";
+
+const HEADER_LEN: usize = 16;
+
+/// The default block size of snappy-java, 32 KiB.
+pub const DEFAULT_BLOCK_SIZE: usize = 32 * 1024;
+
+/// The smallest block size of snappy-java, 1 KiB.
+pub const MIN_BLOCK_SIZE: usize = 1024;
+
+/// The largest block size, as the lengths in a stream are 32-bit signed integers in Java.
+pub const MAX_BLOCK_SIZE: usize = 1 << 30;
+
+/// The version of the format this crate reads and writes.
+const VERSION: u32 = 1;
+
+/// The length of a chunk length.
+const LEN_SIZE: usize = 4;
+
+/// The first 4 bytes of the magic as a chunk length, which start a concatenated stream.
+const MAGIC_HEAD: usize = 0x82534e41;
+
+/// A writer that compresses data to the stream format of snappy-java.
+///
+/// Written data is buffered and emitted as a chunk for every block of data,
+/// like `SnappyOutputStream` does. The header is written with the first chunk,
+/// or by `flush` if nothing was written. Call `flush` to emit the buffered
+/// data. The buffered data is also flushed when the writer is dropped, but
+/// errors are ignored there.
+///
+/// # Examples:
+///
+/// ```rust
+/// use std::io::Write;
+/// use xsnappy::xerial::XerialWriter;
+///
+/// let mut w = XerialWriter::new(vec![]);
+/// w.write_all(b"hello").unwrap();
+/// let stream = w.into_inner().unwrap();
+/// assert_eq!(&stream[..8], b"\x82SNAPPY\x00");
+/// assert_eq!(&stream[16..], b"\x00\x00\x00\x07\x05\x10hello");
+/// ```
+pub struct XerialWriter<W: Write> {
+    inner: BlockWriter<W, XerialEncoder>,
+}
+
+/// Compresses a block to a chunk, after the header for the first one.
+struct XerialEncoder {
+    enc: Encoder,
+    out: Vec<u8>,
+    wrote_header: bool,
+}
+
+impl BlockEncoder for XerialEncoder {
+    fn encode(&mut self, src: &[u8]) -> io::Result<&[u8]> {
+        let mut start = HEADER_LEN;
+        if !self.wrote_header {
+            start = 0;
+            self.out[..HEADER_LEN].copy_from_slice(HEADER);
+            self.wrote_header = true;
+        }
+        let mut end = HEADER_LEN;
+        if !src.is_empty() {
+            let n = self.enc.compress(&mut self.out[HEADER_LEN + LEN_SIZE..], src)?;
+            self.out[HEADER_LEN..HEADER_LEN + LEN_SIZE].copy_from_slice(&(n as u32).to_be_bytes());
+            end += LEN_SIZE + n;
+        }
+        Ok(&self.out[start..end])
+    }
+}
+
+impl<W: Write> XerialWriter<W> {
+    /// Create a writer with snappy-java's default block size, that writes the compressed stream to `w`.
+    pub fn new(w: W) -> XerialWriter<W> {
+        XerialWriter::with_block_size(w, DEFAULT_BLOCK_SIZE)
+    }
+
+    /// Create a writer that emits a chunk for every `block_size` bytes of data.
+    /// # Panics:
+    /// Panics if `block_size` is not from `MIN_BLOCK_SIZE` to `MAX_BLOCK_SIZE`.
+    pub fn with_block_size(w: W, block_size: usize) -> XerialWriter<W> {
+        assert!((MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size),
+                "snappy: xerial block size must be from {} to {}", MIN_BLOCK_SIZE, MAX_BLOCK_SIZE);
+        let enc = XerialEncoder {
+            enc: Encoder::new(),
+            out: vec![0; HEADER_LEN + LEN_SIZE + max_encode_len(block_size)],
+            wrote_header: false,
+        };
+        XerialWriter { inner: BlockWriter::new(w, block_size, enc) }
+    }
+
+    /// Flush the buffered data and return the underlying writer.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.inner.into_inner()
+    }
+
+    /// Return a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.get_ref()
+    }
+
+    /// Return a mutable reference to the underlying writer.
+    /// Writing to it directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.get_mut()
+    }
+}
+
+impl<W: Write> Write for XerialWriter<W> {
+    fn write(&mut self, p: &[u8]) -> io::Result<usize> {
+        self.inner.write(p)
+    }
+
+    /// Emit the buffered data as a chunk and flush the underlying writer.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader that decompresses the stream format of snappy-java.
+///
+/// Like `SnappyInputStream`, it reads data that doesn't start with the magic
+/// as a single Snappy block, and reads concatenated streams as one.
+/// Streams needing a newer version of the format are rejected with
+/// `SnappyError::Unsupported`, chunks with a negative length or claiming more
+/// data than a Snappy block of their length can hold with
+/// `CorruptKind::ChunkLength`, and a stream that ends inside a chunk with
+/// `CorruptKind::TruncatedChunk`.
+/// An error in the stream is returned by every later read.
+///
+/// # Examples:
+///
+/// ```rust
+/// use std::io::{Read, Write};
+/// use xsnappy::xerial::{XerialReader, XerialWriter};
+///
+/// let mut w = XerialWriter::new(vec![]);
+/// w.write_all(b"hello world! hello world!").unwrap();
+/// let stream = w.into_inner().unwrap();
+///
+/// let mut dst = vec![];
+/// XerialReader::new(&stream[..]).read_to_end(&mut dst).unwrap();
+/// assert_eq!(dst, b"hello world! hello world!");
+/// ```
+pub struct XerialReader<R: Read> {
+    inner: BlockReader<R, XerialDecoder>,
+}
+
+/// Reads the chunks of a stream, or the whole data as a block.
+struct XerialDecoder {
+    options: DecodeOptions,
+    body: Vec<u8>,
+    read_header: bool,
+    /// Whether the data was read as a single block.
+    done: bool,
+}
+
+impl<R: Read> XerialReader<R> {
+    /// Create a reader that reads the compressed stream from `r`.
+    pub fn new(r: R) -> XerialReader<R> {
+        XerialReader::with_options(r, DecodeOptions::default())
+    }
+
+    /// Create a reader that fails with `SnappyError::LimitExceeded` once the stream
+    /// decodes to more than `max_decoded_len` bytes in total, or a chunk exceeds `max_ratio`.
+    pub fn with_options(r: R, options: DecodeOptions) -> XerialReader<R> {
+        let dec = XerialDecoder { options, body: Vec::new(), read_header: false, done: false };
+        XerialReader { inner: BlockReader::new(r, dec) }
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner.r
+    }
+
+    /// Return a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner.r
+    }
+
+    /// Return a mutable reference to the underlying reader.
+    /// Reading from it directly will corrupt the stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner.r
+    }
+}
+
+impl XerialDecoder {
+    /// Decode the block in `body`, which starts at input offset `pos`.
+    fn decode_body(&mut self, d: &mut Decoded, pos: usize) -> Result<(), SnappyError> {
+        let n = decode_len(&self.body).map_err(|err| err.shift(pos, d.decoded_len))?;
+        // Don't trust the length to allocate the decoded data either.
+        if n > max_decode_len(self.body.len()) {
+            return Err(d.corrupt(CorruptKind::ChunkLength, pos))
+        }
+        self.options.check_len(d.decoded_len.saturating_add(n))?;
+        self.options.check_ratio(n, self.body.len())?;
+        d.buf.resize(n, 0);
+        decode(&mut d.buf, &self.body).map_err(|err| err.shift(pos, d.decoded_len))?;
+        d.decoded(n);
+        Ok(())
+    }
+
+    /// Read the first chunk, or the whole data as a block if it doesn't start with the magic.
+    /// Return false if the data is empty.
+    fn first_chunk<R: Read>(&mut self, r: &mut R, d: &mut Decoded) -> io::Result<bool> {
+        let mut header = [0; HEADER_LEN];
+        let n = read_full(r, &mut header)?;
+        if n == HEADER_LEN && &header[..MAGIC.len()] == MAGIC {
+            self.read_header = true;
+            d.pos = HEADER_LEN;
+            if let Err(err) = check_version(&header) {
+                return Err(d.fail(err))
+            }
+            return self.next_block(r, d)
+        }
+        self.done = true;
+        self.body.clear();
+        self.body.extend_from_slice(&header[..n]);
+        r.read_to_end(&mut self.body)?;
+        if self.body.is_empty() {
+            return Ok(false)
+        }
+        if let Err(err) = self.decode_body(d, 0) {
+            return Err(d.fail(err))
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> BlockDecoder<R> for XerialDecoder {
+    /// Read and decode the next chunk, skipping the headers of concatenated
+    /// streams. Return false at the end of the stream.
+    fn next_block(&mut self, r: &mut R, d: &mut Decoded) -> io::Result<bool> {
+        if self.done {
+            return Ok(false)
+        }
+        if !self.read_header {
+            return self.first_chunk(r, d)
+        }
+        let mut pos = d.pos;
+        let mut buf = [0; LEN_SIZE];
+        let len = loop {
+            match read_full(r, &mut buf)? {
+                0 => return Ok(false),
+                LEN_SIZE => {}
+                _ => return Err(d.fail(d.corrupt(CorruptKind::TruncatedChunk, pos)))
+            }
+            let len = u32::from_be_bytes(buf) as usize;
+            if len != MAGIC_HEAD {
+                break len
+            }
+            // The header of a concatenated stream.
+            let mut header = [0; HEADER_LEN];
+            header[..LEN_SIZE].copy_from_slice(&MAGIC[..LEN_SIZE]);
+            if read_full(r, &mut header[LEN_SIZE..])? < HEADER_LEN - LEN_SIZE {
+                return Err(d.fail(d.corrupt(CorruptKind::TruncatedChunk, pos)))
+            }
+            if &header[..MAGIC.len()] != MAGIC {
+                return Err(d.fail(d.corrupt(CorruptKind::StreamIdentifier, pos)))
+            }
+            if let Err(err) = check_version(&header) {
+                return Err(d.fail(err))
+            }
+            pos += HEADER_LEN;
+            d.pos = pos;
+        };
+        if len > i32::MAX as usize {
+            return Err(d.fail(d.corrupt(CorruptKind::ChunkLength, pos)))
+        }
+        // Read the body as it comes rather than trusting the length to allocate it.
+        self.body.clear();
+        if r.by_ref().take(len as u64).read_to_end(&mut self.body)? < len {
+            return Err(d.fail(d.corrupt(CorruptKind::TruncatedChunk, pos)))
+        }
+        d.pos += LEN_SIZE + len;
+        if let Err(err) = self.decode_body(d, pos + LEN_SIZE) {
+            return Err(d.fail(err))
+        }
+        Ok(true)
+    }
+}
+
+/// Check that the stream of `header` can be read with version 1 of the format.
+fn check_version(header: &[u8; HEADER_LEN]) -> Result<(), SnappyError> {
+    let compatible = u32::from_be_bytes([header[12], header[13], header[14], header[15]]);
+    if compatible > VERSION {
+        return Err(SnappyError::Unsupported)
+    }
+    Ok(())
+}
+
+impl<R: Read> Read for XerialReader<R> {
+    fn read(&mut self, p: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(p)
+    }
+}
+
+impl<R: Read> BufRead for XerialReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn xsnappy(dir: &PathBuf, args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_xsnappy"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Usage errors exit before reading stdin, closing the pipe.
+    let _ = child.stdin.take().unwrap().write_all(stdin);
+    child.wait_with_output().unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("xsnappy-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn text() -> Vec<u8> {
+    b"hello world! ".iter().cycle().take(100000).cloned().collect()
+}
+
+#[test]
+fn test_files() {
+    let dir = temp_dir("files");
+    fs::write(dir.join("a.txt"), text()).unwrap();
+
+    // Compressing replaces the file.
+    assert!(xsnappy(&dir, &["compress", "a.txt"], b"").status.success());
+    assert!(!dir.join("a.txt").exists());
+    assert_eq!(&fs::read(dir.join("a.txt.sz")).unwrap()[..10], b"\xff\x06\x00\x00sNaPpY");
+
+    // -k keeps it.
+    assert!(xsnappy(&dir, &["decompress", "-k", "a.txt.sz"], b"").status.success());
+    assert!(dir.join("a.txt.sz").exists());
+    assert_eq!(fs::read(dir.join("a.txt")).unwrap(), text());
+
+    // The output is not overwritten without -f.
+    let out = xsnappy(&dir, &["compress", "--format", "raw", "-k", "a.txt"], b"");
+    assert!(out.status.success());
+    fs::write(dir.join("a.txt.snappy"), b"old").unwrap();
+    let out = xsnappy(&dir, &["compress", "--format=raw", "-k", "a.txt"], b"");
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("already exists"));
+    assert_eq!(fs::read(dir.join("a.txt.snappy")).unwrap(), b"old");
+    assert!(xsnappy(&dir, &["compress", "--format=raw", "-kf", "a.txt"], b"").status.success());
+
+    // -c writes to stdout and keeps the file.
+    let out = xsnappy(&dir, &["decompress", "--format", "raw", "-c", "a.txt.snappy"], b"");
+    assert!(out.status.success());
+    assert_eq!(out.stdout, text());
+    assert!(dir.join("a.txt.snappy").exists());
+
+    // Unknown suffixes are skipped.
+    assert_eq!(xsnappy(&dir, &["decompress", "a.txt"], b"").status.code(), Some(2));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_stdin() {
+    let dir = temp_dir("stdin");
+    for format in ["raw", "framed"].iter() {
+        let compressed = xsnappy(&dir, &["compress", "-f", "--format", format], &text());
+        assert!(compressed.status.success());
+        let out = xsnappy(&dir, &["decompress", "--format", format, "-"], &compressed.stdout);
+        assert!(out.status.success());
+        assert_eq!(out.stdout, text());
+
+        // Corrupt input fails with the error of the decoder.
+        let out = xsnappy(&dir, &["decompress", "--format", format], &compressed.stdout[..100]);
+        assert_eq!(out.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&out.stderr).starts_with("xsnappy: stdin: snappy: corrupt input"));
+    }
+    assert_eq!(xsnappy(&dir, &["frob"], b"").status.code(), Some(1));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_inspect() {
+    let dir = temp_dir("inspect");
+    let raw = xsnappy(&dir, &["compress", "-f", "--format", "raw"], &text()).stdout;
+    let out = xsnappy(&dir, &["inspect"], &raw);
+    assert!(out.status.success());
+    let report = String::from_utf8(out.stdout).unwrap();
+    assert!(report.contains("format: raw"));
+    assert!(report.contains("header: a0 8d 06 (3 bytes)"));
+    assert!(report.contains("decoded length: 100000"));
+    assert!(report.contains("copy lengths:"));
+
+    let framed = xsnappy(&dir, &["compress", "-f"], &text()).stdout;
+    let out = xsnappy(&dir, &["inspect"], &framed);
+    assert!(out.status.success());
+    let report = String::from_utf8(out.stdout).unwrap();
+    assert!(report.contains("format: framed"));
+    assert_eq!(report.matches("crc ok").count(), 2);
+
+    // A damaged checksum is reported on its chunk, the later chunks are still listed, and it fails.
+    let mut long = xsnappy(&dir, &["compress", "-f"], &text().repeat(2)).stdout;
+    let second = 10 + 4 + (long[11] as usize | (long[12] as usize) << 8 | (long[13] as usize) << 16);
+    long[second + 4] ^= 1;
+    let out = xsnappy(&dir, &["inspect"], &long);
+    assert_eq!(out.status.code(), Some(1));
+    let report = String::from_utf8(out.stdout).unwrap();
+    let rows: Vec<&str> = report.lines().filter(|l| l.contains("crc")).collect();
+    assert_eq!(rows.len(), 4);
+    assert!(rows[1].trim_start().starts_with(&second.to_string()) && rows[1].ends_with("crc mismatch"));
+    assert!(rows[2].ends_with("crc ok") && rows[3].ends_with("crc ok"));
+    assert!(report.contains("chunks: 5,") && report.contains("checksum mismatches: 1"));
+    // A truncated stream fails where `decompress` fails.
+    let out = xsnappy(&dir, &["inspect"], &framed[..framed.len() - 1]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8(out.stdout).unwrap().contains("chunk cut off by the end of input"));
+
+    // The options of compress and decompress are usage errors.
+    for opt in ["-c", "-k", "-f", "--keep"].iter() {
+        let out = xsnappy(&dir, &["inspect", opt], &framed);
+        assert_eq!(out.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&out.stderr).contains("inspect takes no -c, -k or -f"));
+        assert!(out.stdout.is_empty());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}